@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Hash, Eq)]
 pub struct Colour {
     pub red: u8,
     pub blue: u8,
@@ -16,6 +16,21 @@ impl Colour {
         }
     }
 
+    pub fn with_alpha(mut self, alpha: u8) -> Self {
+        self.alpha = alpha;
+        self
+    }
+
+    /// Gets the colour as rgba values from 0.0 to 1.0, the way shaders expect them.
+    pub fn to_rgba_f32(self) -> [f32; 4] {
+        [
+            self.red as f32 / 255.0,
+            self.green as f32 / 255.0,
+            self.blue as f32 / 255.0,
+            self.alpha as f32 / 255.0,
+        ]
+    }
+
     pub fn white() -> Self {
         Self {
             red: 255,
@@ -1,9 +1,19 @@
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Transform {
+    /// The position of the texture's origin. This isn't affected by `scale`, so scaling something
+    /// doesn't move it.
     pub x: f32,
     pub y: f32,
     pub z: f32,
-    pub scale: f32,
+
+    /// The horizontal and vertical scale.
+    pub scale: (f32, f32),
+
+    /// The clockwise rotation in radians, around the texture's origin.
+    pub rotation: f32,
+
+    /// Whether to mirror horizontally around the texture's origin, ex. a character facing right.
+    pub flip_x: bool,
 }
 
 impl Transform {
@@ -12,7 +22,7 @@ impl Transform {
             x,
             y,
             z,
-            scale: 1.0,
+            ..Default::default()
         }
     }
 }
@@ -23,7 +33,9 @@ impl Default for Transform {
             x: Default::default(),
             y: Default::default(),
             z: Default::default(),
-            scale: 1.0,
+            scale: (1.0, 1.0),
+            rotation: Default::default(),
+            flip_x: false,
         }
     }
 }
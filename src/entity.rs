@@ -0,0 +1,245 @@
+use std::{
+    any::TypeId,
+    collections::{BTreeMap, HashMap},
+};
+
+use downcast_rs::{impl_downcast, Downcast};
+
+/// A game object, ex. a sprite, a button or (eventually) a player or mob.
+/// Entities are just ids, what they are is decided by the components attached to them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Entity(u64);
+
+pub trait Component: 'static {}
+
+impl<T> Component for T where T: 'static {}
+
+/// Entities that belong to another, ex. the glyphs drawing a text input's text.
+/// They're despawned along with it, see `Entities::despawn`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Children(pub Vec<Entity>);
+
+/// The scene an entity was spawned in. Added automatically, so everything a scene spawned is
+/// despawned when it exits.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SceneMember(pub &'static str);
+
+/// Every entity in the game, along with their components.
+///
+/// Components are stored per type, so systems can query for every entity with a given set of
+/// components, ex. everything with a `Transform` and an `Animation`, without each kind of game
+/// object needing its own storage.
+#[derive(Default)]
+pub struct Entities {
+    next_entity: u64,
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+
+    /// The scene entities are currently being spawned in, if any.
+    scene: Option<&'static str>,
+}
+
+impl Entities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new entity, which components can be added to with the returned builder.
+    pub fn spawn(&mut self) -> EntityBuilder<'_> {
+        let entity = Entity(self.next_entity);
+        self.next_entity += 1;
+
+        if let Some(scene) = self.scene {
+            self.insert(entity, SceneMember(scene));
+        }
+
+        EntityBuilder {
+            entities: self,
+            entity,
+        }
+    }
+
+    /// Removes an entity and all of its components, along with its `Children`.
+    /// Anything the renderer holds for it, ex. its textures, is released once it notices the
+    /// entity is gone.
+    pub fn despawn(&mut self, entity: Entity) {
+        self.despawn_children(entity);
+
+        for storage in self.storages.values_mut() {
+            storage.remove(entity);
+        }
+    }
+
+    /// Despawns an entity's `Children`, but not the entity itself, ex. to replace them.
+    pub fn despawn_children(&mut self, entity: Entity) {
+        let children = self.storage_mut::<Children>().components.remove(&entity);
+
+        for child in children.into_iter().flat_map(|Children(children)| children) {
+            self.despawn(child);
+        }
+    }
+
+    /// Despawns every entity spawned in `scene`.
+    pub fn despawn_scene(&mut self, scene: &str) {
+        self.despawn_where::<SceneMember>(|SceneMember(entity_scene)| *entity_scene == scene);
+    }
+
+    fn despawn_where<T: Component>(&mut self, predicate: impl Fn(&T) -> bool) {
+        let entities: Vec<Entity> = self
+            .query::<T>()
+            .filter(|(_, component)| predicate(component))
+            .map(|(entity, _)| entity)
+            .collect();
+
+        for entity in entities {
+            self.despawn(entity);
+        }
+    }
+
+    /// Sets the scene entities are spawned in, or `None` when no scene is running.
+    pub fn set_scene(&mut self, scene: Option<&'static str>) {
+        self.scene = scene;
+    }
+
+    /// Attaches a component to an entity, replacing the entity's existing component of that type.
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) {
+        self.storage_mut::<T>().components.insert(entity, component);
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.storage::<T>()?.components.get(&entity)
+    }
+
+    /// Gets every entity with a `T` component.
+    pub fn query<T: Component>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.storage::<T>()
+            .into_iter()
+            .flat_map(|storage| storage.components.iter())
+            .map(|(entity, component)| (*entity, component))
+    }
+
+    pub fn query_mut<T: Component>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.storage_mut::<T>()
+            .components
+            .iter_mut()
+            .map(|(entity, component)| (*entity, component))
+    }
+
+    fn storage<T: Component>(&self) -> Option<&ComponentStorage<T>> {
+        self.storages
+            .get(&TypeId::of::<T>())
+            .map(|storage| storage.downcast_ref::<ComponentStorage<T>>().unwrap())
+    }
+
+    /// Gets the storage for `T` components, creating it the first time it's needed.
+    fn storage_mut<T: Component>(&mut self) -> &mut ComponentStorage<T> {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(ComponentStorage::<T>::new()))
+            .downcast_mut::<ComponentStorage<T>>()
+            .unwrap()
+    }
+}
+
+/// Adds components to a newly spawned entity.
+pub struct EntityBuilder<'a> {
+    entities: &'a mut Entities,
+    entity: Entity,
+}
+
+impl EntityBuilder<'_> {
+    pub fn with<T: Component>(self, component: T) -> Self {
+        self.entities.insert(self.entity, component);
+        self
+    }
+
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+}
+
+/// Every component of one type, keyed by the entity it's attached to.
+///
+/// Entities are kept in the order they were spawned in, so queries visit them in the same order
+/// every frame, ex. so overlapping items are always drawn the same way.
+struct ComponentStorage<T> {
+    components: BTreeMap<Entity, T>,
+}
+
+impl<T> ComponentStorage<T> {
+    fn new() -> Self {
+        Self {
+            components: BTreeMap::new(),
+        }
+    }
+}
+
+/// A `ComponentStorage` of any type, so storages for every component type can be kept together.
+trait AnyStorage: Downcast {
+    fn remove(&mut self, entity: Entity);
+}
+
+impl<T: Component> AnyStorage for ComponentStorage<T> {
+    fn remove(&mut self, entity: Entity) {
+        self.components.remove(&entity);
+    }
+}
+
+impl_downcast!(AnyStorage);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn despawning_despawns_children() {
+        let mut entities = Entities::new();
+        let grandchild = entities.spawn().with(3).id();
+        let child = entities
+            .spawn()
+            .with(2)
+            .with(Children(vec![grandchild]))
+            .id();
+        let parent = entities.spawn().with(1).with(Children(vec![child])).id();
+        let unrelated = entities.spawn().with(4).id();
+
+        entities.despawn(parent);
+
+        assert_eq!(entities.get::<i32>(parent), None);
+        assert_eq!(entities.get::<i32>(child), None);
+        assert_eq!(entities.get::<i32>(grandchild), None);
+        assert_eq!(entities.get::<i32>(unrelated), Some(&4));
+    }
+
+    #[test]
+    fn despawning_children_keeps_the_parent() {
+        let mut entities = Entities::new();
+        let child = entities.spawn().with(2).id();
+        let parent = entities.spawn().with(1).with(Children(vec![child])).id();
+
+        entities.despawn_children(parent);
+
+        assert_eq!(entities.get::<i32>(parent), Some(&1));
+        assert_eq!(entities.get::<Children>(parent), None);
+        assert_eq!(entities.get::<i32>(child), None);
+    }
+
+    #[test]
+    fn despawning_a_scene_despawns_its_entities_children() {
+        let mut entities = Entities::new();
+
+        // Children spawned outside of the scene still belong to the entity that holds them.
+        let child = entities.spawn().with(2).id();
+
+        entities.set_scene(Some("login"));
+        let parent = entities.spawn().with(1).with(Children(vec![child])).id();
+        entities.set_scene(None);
+
+        let outside = entities.spawn().with(3).id();
+
+        entities.despawn_scene("login");
+
+        assert_eq!(entities.get::<i32>(parent), None);
+        assert_eq!(entities.get::<i32>(child), None);
+        assert_eq!(entities.get::<i32>(outside), Some(&3));
+    }
+}
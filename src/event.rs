@@ -0,0 +1,175 @@
+use std::marker::PhantomData;
+
+use winit::event::KeyEvent;
+
+use crate::entity::Entity;
+
+/// A button was clicked, ie. pressed and released while the cursor was over it.
+#[derive(Copy, Clone, Debug)]
+pub struct ButtonClicked(pub Entity);
+
+/// Enter was pressed in a text input.
+#[derive(Clone, Debug)]
+pub struct TextSubmitted {
+    pub input: Entity,
+    pub text: String,
+}
+
+/// A key was pressed, or repeated while held down.
+#[derive(Clone, Debug)]
+pub struct KeyPressed(pub KeyEvent);
+
+/// Every event of one type sent in the current and previous update.
+///
+/// Events are double buffered: they're kept for two updates, so a system that runs before the
+/// sender in an update still sees them in the next one. Each reader tracks what it's already read
+/// with an `EventReader`, so it only sees each event once.
+pub struct Events<T> {
+    previous: Vec<T>,
+    current: Vec<T>,
+
+    /// The id of the first event in `previous`. Ids are the number of events sent before it.
+    previous_start: usize,
+}
+
+impl<T> Events<T> {
+    pub fn new() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            previous_start: 0,
+        }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push(event);
+    }
+
+    /// Drops the events from the previous update and starts buffering a new one.
+    /// This is called once at the start of every update, see `State::update_events`.
+    pub fn update(&mut self) {
+        self.previous_start += self.previous.len();
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    /// The id the next event sent will have.
+    fn next_id(&self) -> usize {
+        self.previous_start + self.previous.len() + self.current.len()
+    }
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads the events of one type that haven't been read yet.
+/// Anything more than an update old when it's read is missed, so it should be read every update.
+pub struct EventReader<T> {
+    next_id: usize,
+    _event: PhantomData<fn() -> T>,
+}
+
+impl<T> EventReader<T> {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            _event: PhantomData,
+        }
+    }
+
+    /// Gets every event sent since the last time this reader was used, oldest first.
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> {
+        let skip = self.next_id.saturating_sub(events.previous_start);
+        self.next_id = events.next_id();
+
+        events
+            .previous
+            .iter()
+            .chain(events.current.iter())
+            .skip(skip)
+    }
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(reader: &mut EventReader<u32>, events: &Events<u32>) -> Vec<u32> {
+        reader.read(events).copied().collect()
+    }
+
+    #[test]
+    fn events_are_read_once() {
+        let mut events = Events::new();
+        let mut reader = EventReader::new();
+
+        events.send(1);
+        events.send(2);
+        assert_eq!(read(&mut reader, &events), vec![1, 2]);
+        assert_eq!(read(&mut reader, &events), Vec::<u32>::new());
+
+        events.send(3);
+        assert_eq!(read(&mut reader, &events), vec![3]);
+    }
+
+    #[test]
+    fn events_are_kept_for_an_update() {
+        let mut events = Events::new();
+        let mut reader = EventReader::new();
+
+        events.send(1);
+        events.update();
+        events.send(2);
+
+        // The reader runs after both updates' events were sent, so it sees both.
+        assert_eq!(read(&mut reader, &events), vec![1, 2]);
+
+        events.update();
+        events.send(3);
+
+        // 2 moved into the previous buffer, but it was already read.
+        assert_eq!(read(&mut reader, &events), vec![3]);
+    }
+
+    #[test]
+    fn events_older_than_an_update_are_missed() {
+        let mut events = Events::new();
+        let mut reader = EventReader::new();
+
+        events.send(1);
+        assert_eq!(read(&mut reader, &events), vec![1]);
+
+        events.send(2);
+        events.update();
+        events.send(3);
+        events.update();
+        events.send(4);
+
+        // 2 was dropped before the reader got to it, the rest are still there.
+        assert_eq!(read(&mut reader, &events), vec![3, 4]);
+    }
+
+    #[test]
+    fn readers_are_independent() {
+        let mut events = Events::new();
+        let mut first = EventReader::new();
+        let mut second = EventReader::new();
+
+        events.send(1);
+        assert_eq!(read(&mut first, &events), vec![1]);
+
+        events.update();
+        events.send(2);
+
+        assert_eq!(read(&mut first, &events), vec![2]);
+        assert_eq!(read(&mut second, &events), vec![1, 2]);
+    }
+}
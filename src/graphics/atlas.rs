@@ -0,0 +1,307 @@
+use super::PixelFormat;
+
+/// The width and height of a shared atlas page.
+/// Textures bigger than this get a page of their own.
+pub const ATLAS_PAGE_SIZE: u32 = 2048;
+
+/// Empty space left around each region so linear filtering doesn't bleed into its neighbours.
+const PADDING: u32 = 1;
+
+/// Packs textures into large shared pages, so many items can be drawn with a single bind group.
+///
+/// Pages are filled with simple shelf packing: regions are placed left to right in rows
+/// ("shelves"), and a new shelf is started below the last one when a region doesn't fit.
+/// Freed regions are reused for textures that fit in them, and a page is reset once all of its
+/// regions have been freed.
+///
+/// Each page holds a single pixel format, so textures only share pages with textures in the same
+/// format.
+pub struct Atlas {
+    pages: Vec<AtlasPage>,
+}
+
+impl Atlas {
+    pub fn new() -> Self {
+        Self { pages: Vec::new() }
+    }
+
+    /// Finds space for a `width` x `height` texture, creating a new page if none of the existing
+    /// pages in its format have room for it.
+    pub fn allocate(&mut self, width: u32, height: u32, format: PixelFormat) -> AtlasRegion {
+        let padded_width = width + PADDING * 2;
+        let padded_height = height + PADDING * 2;
+
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            if page.format != format {
+                continue;
+            }
+
+            if let Some(slot) = page.allocate(padded_width, padded_height) {
+                return AtlasRegion::new(index, page.size, slot, width, height);
+            }
+        }
+
+        let size = ATLAS_PAGE_SIZE.max(padded_width).max(padded_height);
+        let mut page = AtlasPage::new(size, format);
+        let slot = page
+            .allocate(padded_width, padded_height)
+            .expect("texture should fit in an empty page");
+
+        let region = AtlasRegion::new(self.pages.len(), page.size, slot, width, height);
+
+        self.pages.push(page);
+        region
+    }
+
+    /// Frees a region so its space can be reused.
+    /// Returns whether this emptied the region's page, in which case the page has been reset.
+    pub fn deallocate(&mut self, region: &AtlasRegion) -> bool {
+        self.pages[region.page].deallocate(region.slot)
+    }
+
+    /// Gets the size of an atlas page.
+    pub fn page_size(&self, page: usize) -> u32 {
+        self.pages[page].size
+    }
+
+    /// Gets the pixel format of an atlas page.
+    pub fn page_format(&self, page: usize) -> PixelFormat {
+        self.pages[page].format
+    }
+}
+
+/// A texture's location in an atlas page, in pixels.
+#[derive(Copy, Clone, Debug)]
+pub struct AtlasRegion {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    page_size: u32,
+
+    /// The space reserved for the region, including padding.
+    slot: Slot,
+}
+
+impl AtlasRegion {
+    fn new(page: usize, page_size: u32, slot: Slot, width: u32, height: u32) -> Self {
+        Self {
+            page,
+            x: slot.x + PADDING,
+            y: slot.y + PADDING,
+            width,
+            height,
+            page_size,
+            slot,
+        }
+    }
+
+    /// Maps a rect in texture coordinates relative to the original texture onto the atlas page.
+    pub fn map_uv_rect(&self, uv_rect: [f32; 4]) -> [f32; 4] {
+        let page_size = self.page_size as f32;
+        let width = self.width as f32;
+        let height = self.height as f32;
+
+        [
+            (self.x as f32 + uv_rect[0] * width) / page_size,
+            (self.y as f32 + uv_rect[1] * height) / page_size,
+            uv_rect[2] * width / page_size,
+            uv_rect[3] * height / page_size,
+        ]
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Slot {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+struct AtlasPage {
+    size: u32,
+    format: PixelFormat,
+    shelves: Vec<Shelf>,
+    free_slots: Vec<Slot>,
+
+    /// The number of regions currently allocated in the page.
+    allocations: usize,
+}
+
+impl AtlasPage {
+    fn new(size: u32, format: PixelFormat) -> Self {
+        Self {
+            size,
+            format,
+            shelves: Vec::new(),
+            free_slots: Vec::new(),
+            allocations: 0,
+        }
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<Slot> {
+        let slot = self
+            .allocate_free_slot(width, height)
+            .or_else(|| self.allocate_on_shelf(width, height))?;
+
+        self.allocations += 1;
+        Some(slot)
+    }
+
+    /// Reuses the smallest freed slot the region fits in.
+    fn allocate_free_slot(&mut self, width: u32, height: u32) -> Option<Slot> {
+        let (index, _) = self
+            .free_slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.width >= width && slot.height >= height)
+            .min_by_key(|(_, slot)| slot.width * slot.height)?;
+
+        Some(self.free_slots.swap_remove(index))
+    }
+
+    fn allocate_on_shelf(&mut self, width: u32, height: u32) -> Option<Slot> {
+        let size = self.size;
+
+        if width > size {
+            return None;
+        }
+
+        // Use the shortest shelf the region fits in, to waste as little vertical space as possible.
+        let best_shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && shelf.next_x + width <= size)
+            .min_by_key(|shelf| shelf.height);
+
+        if let Some(shelf) = best_shelf {
+            let x = shelf.next_x;
+            shelf.next_x += width;
+
+            return Some(Slot {
+                x,
+                y: shelf.y,
+                width,
+                height: shelf.height,
+            });
+        }
+
+        let next_y = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height);
+
+        if next_y + height > size {
+            return None;
+        }
+
+        self.shelves.push(Shelf {
+            y: next_y,
+            height,
+            next_x: width,
+        });
+
+        Some(Slot {
+            x: 0,
+            y: next_y,
+            width,
+            height,
+        })
+    }
+
+    fn deallocate(&mut self, slot: Slot) -> bool {
+        self.allocations -= 1;
+
+        if self.allocations > 0 {
+            self.free_slots.push(slot);
+            return false;
+        }
+
+        // Start over with a regular sized page, in case this was a page for an oversized texture.
+        *self = Self::new(ATLAS_PAGE_SIZE, self.format);
+        true
+    }
+}
+
+struct Shelf {
+    y: u32,
+    height: u32,
+    next_x: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn freed_slots_are_reused() {
+        let mut atlas = Atlas::new();
+        let first = atlas.allocate(10, 10, PixelFormat::Bgra8);
+        let _second = atlas.allocate(10, 10, PixelFormat::Bgra8);
+
+        assert!(!atlas.deallocate(&first));
+
+        let reused = atlas.allocate(8, 8, PixelFormat::Bgra8);
+        assert_eq!(
+            (reused.page, reused.x, reused.y),
+            (first.page, first.x, first.y)
+        );
+    }
+
+    #[test]
+    fn freed_slots_too_small_are_skipped() {
+        let mut atlas = Atlas::new();
+        let small = atlas.allocate(10, 10, PixelFormat::Bgra8);
+        let _other = atlas.allocate(10, 10, PixelFormat::Bgra8);
+
+        atlas.deallocate(&small);
+
+        let large = atlas.allocate(20, 20, PixelFormat::Bgra8);
+        assert_ne!((large.x, large.y), (small.x, small.y));
+    }
+
+    #[test]
+    fn smallest_freed_slot_is_reused() {
+        let mut atlas = Atlas::new();
+        let large = atlas.allocate(50, 50, PixelFormat::Bgra8);
+        let small = atlas.allocate(10, 10, PixelFormat::Bgra8);
+        let _other = atlas.allocate(10, 10, PixelFormat::Bgra8);
+
+        atlas.deallocate(&large);
+        atlas.deallocate(&small);
+
+        let reused = atlas.allocate(5, 5, PixelFormat::Bgra8);
+        assert_eq!((reused.x, reused.y), (small.x, small.y));
+    }
+
+    #[test]
+    fn oversized_pages_are_reset_once_empty() {
+        let mut atlas = Atlas::new();
+        let oversized = atlas.allocate(ATLAS_PAGE_SIZE + 100, 10, PixelFormat::Bgra8);
+
+        assert!(atlas.page_size(oversized.page) > ATLAS_PAGE_SIZE);
+        assert!(atlas.deallocate(&oversized));
+        assert_eq!(atlas.page_size(oversized.page), ATLAS_PAGE_SIZE);
+
+        // The reset page is empty, so the next texture starts in its corner again.
+        let region = atlas.allocate(10, 10, PixelFormat::Bgra8);
+        assert_eq!(
+            (region.page, region.x, region.y),
+            (oversized.page, PADDING, PADDING)
+        );
+    }
+
+    #[test]
+    fn pages_are_kept_per_format() {
+        let mut atlas = Atlas::new();
+        let bgra = atlas.allocate(10, 10, PixelFormat::Bgra8);
+        let rgba = atlas.allocate(10, 10, PixelFormat::Rgba8);
+        let other_bgra = atlas.allocate(10, 10, PixelFormat::Bgra8);
+
+        assert_ne!(bgra.page, rgba.page);
+        assert_eq!(bgra.page, other_bgra.page);
+        assert_eq!(atlas.page_format(rgba.page), PixelFormat::Rgba8);
+    }
+}
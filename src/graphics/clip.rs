@@ -0,0 +1,88 @@
+use winit::dpi::PhysicalSize;
+
+/// A rectangle, in virtual pixels, that an item is clipped to when drawn.
+/// Anything outside of it isn't drawn, ex. text that overflows a text input.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ClipRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ClipRect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Converts the rect to a scissor rect (x, y, width, height), clamped to the render target so
+    /// wgpu accepts it.
+    ///
+    /// Clipped items are always drawn into the scene texture at the virtual resolution, so one
+    /// virtual pixel is one pixel of the target. The window's scale factor only comes into play
+    /// when the compositor scales the scene up to the window, after clipping.
+    pub fn to_scissor_rect(self, target_size: PhysicalSize<u32>) -> [u32; 4] {
+        // Round outwards so partially covered pixels aren't cut off.
+        let left = self.x.floor().max(0.0) as u32;
+        let top = self.y.floor().max(0.0) as u32;
+        let right = (self.x + self.width).ceil().max(0.0) as u32;
+        let bottom = (self.y + self.height).ceil().max(0.0) as u32;
+
+        let left = left.min(target_size.width);
+        let top = top.min(target_size.height);
+        let right = right.clamp(left, target_size.width);
+        let bottom = bottom.clamp(top, target_size.height);
+
+        [left, top, right - left, bottom - top]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: PhysicalSize<u32> = PhysicalSize::new(800, 600);
+
+    #[test]
+    fn whole_pixel_rects_are_unchanged() {
+        let clip = ClipRect::new(10.0, 20.0, 30.0, 40.0);
+
+        assert_eq!(clip.to_scissor_rect(TARGET), [10, 20, 30, 40]);
+    }
+
+    #[test]
+    fn partially_covered_pixels_are_kept() {
+        let clip = ClipRect::new(10.5, 20.25, 30.0, 40.0);
+
+        // The right edge is at 40.5 and the bottom at 60.25, so both round out to the next pixel.
+        assert_eq!(clip.to_scissor_rect(TARGET), [10, 20, 31, 41]);
+    }
+
+    #[test]
+    fn negative_origins_are_clamped_to_the_target() {
+        let clip = ClipRect::new(-10.5, -20.0, 30.0, 40.0);
+
+        assert_eq!(clip.to_scissor_rect(TARGET), [0, 0, 20, 20]);
+    }
+
+    #[test]
+    fn rects_past_the_target_are_clamped_to_it() {
+        let clip = ClipRect::new(790.0, 590.0, 30.0, 40.0);
+
+        assert_eq!(clip.to_scissor_rect(TARGET), [790, 590, 10, 10]);
+    }
+
+    #[test]
+    fn rects_outside_the_target_are_empty() {
+        let left = ClipRect::new(-50.0, 10.0, 20.0, 20.0);
+        let right = ClipRect::new(900.0, 10.0, 20.0, 20.0);
+
+        assert_eq!(left.to_scissor_rect(TARGET), [0, 10, 0, 20]);
+        assert_eq!(right.to_scissor_rect(TARGET), [800, 10, 0, 20]);
+    }
+}
@@ -0,0 +1,367 @@
+use winit::dpi::PhysicalSize;
+
+use crate::resource::{ColourFilter, ScreenEffects};
+
+use super::Shader;
+
+/// Parameters for the composite pass, see `shaders/composite.wgsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PostProcessUniform {
+    /// The colour filter's matrix, in column-major order.
+    pub colour_matrix: [[f32; 4]; 4],
+    pub colour_offset: [f32; 4],
+    pub fade: f32,
+
+    // Uniforms are padded to a multiple of 16 bytes.
+    _padding: [f32; 3],
+}
+
+impl PostProcessUniform {
+    pub fn compute(effects: &ScreenEffects) -> Self {
+        let filter = effects.colour_filter;
+
+        Self {
+            colour_matrix: transpose(filter.matrix),
+            colour_offset: filter.offset,
+            fade: effects.fade_amount(),
+            _padding: [0.0; 3],
+        }
+    }
+
+    /// Whether the effects leave the scene unchanged, in which case the composite pass is skipped.
+    pub fn is_identity(&self) -> bool {
+        let identity = ColourFilter::identity();
+
+        self.colour_matrix == transpose(identity.matrix)
+            && self.colour_offset == identity.offset
+            && self.fade == 0.0
+    }
+}
+
+impl Default for PostProcessUniform {
+    fn default() -> Self {
+        Self::compute(&ScreenEffects::new())
+    }
+}
+
+fn transpose(matrix: [[f32; 4]; 4]) -> [[f32; 4]; 4] {
+    let mut transposed = [[0.0; 4]; 4];
+
+    for (i, row) in matrix.iter().enumerate() {
+        for (j, value) in row.iter().enumerate() {
+            transposed[j][i] = *value;
+        }
+    }
+
+    transposed
+}
+
+/// Draws the scene into an offscreen texture at the virtual resolution, then scales that texture
+/// into the frame with a fullscreen pass that also applies any screen effects.
+pub struct Compositor {
+    render_pipeline: wgpu::RenderPipeline,
+
+    // Kept to rebuild the render pipeline when its shader is reloaded.
+    #[cfg(debug_assertions)]
+    render_pipeline_layout: wgpu::PipelineLayout,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    uniform_buffer: wgpu::Buffer,
+    uniform: PostProcessUniform,
+    format: wgpu::TextureFormat,
+
+    /// The offscreen texture the scene is drawn into, created the first time it's needed and
+    /// whenever the virtual resolution changes.
+    scene: Option<(wgpu::Texture, wgpu::BindGroup)>,
+}
+
+impl Compositor {
+    pub const SHADER: Shader = Shader {
+        file_name: "composite.wgsl",
+        source: include_str!("shaders/composite.wgsl"),
+    };
+
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        filter_mode: wgpu::FilterMode,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("composite bind group layout"),
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("composite render pipeline layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let shader = Self::SHADER.create_module(device);
+        let render_pipeline =
+            create_render_pipeline(device, &render_pipeline_layout, &shader, format);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("composite sampler"),
+            mag_filter: filter_mode,
+            min_filter: filter_mode,
+            ..Default::default()
+        });
+
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("post process buffer"),
+            size: std::mem::size_of::<PostProcessUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            render_pipeline,
+            #[cfg(debug_assertions)]
+            render_pipeline_layout,
+            bind_group_layout,
+            sampler,
+            uniform_buffer,
+            uniform: PostProcessUniform::default(),
+            format,
+            scene: None,
+        }
+    }
+
+    /// Builds the composite render pipeline from a reloaded shader, see `set_render_pipeline`.
+    #[cfg(debug_assertions)]
+    pub fn create_render_pipeline(
+        &self,
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+    ) -> wgpu::RenderPipeline {
+        create_render_pipeline(device, &self.render_pipeline_layout, shader, self.format)
+    }
+
+    #[cfg(debug_assertions)]
+    pub fn set_render_pipeline(&mut self, render_pipeline: wgpu::RenderPipeline) {
+        self.render_pipeline = render_pipeline;
+    }
+
+    /// Updates the effects, returning whether they changed and had to be uploaded.
+    pub fn update(&mut self, queue: &wgpu::Queue, uniform: PostProcessUniform) -> bool {
+        if uniform == self.uniform {
+            return false;
+        }
+
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.uniform = uniform;
+        true
+    }
+
+    /// Whether any screen effects need to be applied to the scene.
+    pub fn has_effects(&self) -> bool {
+        !self.uniform.is_identity()
+    }
+
+    /// Gets a view of the texture the scene should be drawn into, (re)creating it if it isn't
+    /// `size`.
+    pub fn scene_view(
+        &mut self,
+        device: &wgpu::Device,
+        size: PhysicalSize<u32>,
+    ) -> wgpu::TextureView {
+        let up_to_date = self.scene.as_ref().is_some_and(|(texture, _)| {
+            texture.width() == size.width && texture.height() == size.height
+        });
+
+        if !up_to_date {
+            self.scene = Some(self.create_scene(device, size));
+        }
+
+        let (texture, _) = self.scene.as_ref().unwrap();
+        texture.create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn create_scene(
+        &self,
+        device: &wgpu::Device,
+        size: PhysicalSize<u32>,
+    ) -> (wgpu::Texture, wgpu::BindGroup) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            // Use the frame's format so scene pipelines can draw into either.
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            label: Some("scene texture"),
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+            label: Some("scene bind group"),
+        });
+
+        (texture, bind_group)
+    }
+
+    /// Draws the scene into the `rect` (x, y, width, height) area of `view` with the effects
+    /// applied. The rest of `view` is cleared to black.
+    pub fn draw(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        rect: [f32; 4],
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let Some((_, bind_group)) = &self.scene else {
+            return;
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("composite render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes,
+        });
+
+        let [x, y, width, height] = rect;
+        render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+fn create_render_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("composite render pipeline"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: Some("vs_main"),
+            buffers: &[],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: None,
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn no_effects_is_the_identity() {
+        assert!(PostProcessUniform::default().is_identity());
+    }
+
+    #[test]
+    fn fading_is_not_the_identity() {
+        let mut effects = ScreenEffects::new();
+        effects.fade_out(Duration::ZERO);
+
+        assert!(!PostProcessUniform::compute(&effects).is_identity());
+    }
+
+    #[test]
+    fn colour_filters_are_not_the_identity() {
+        let mut effects = ScreenEffects::new();
+        effects.colour_filter.offset = [0.1, 0.0, 0.0, 0.0];
+
+        assert!(!PostProcessUniform::compute(&effects).is_identity());
+
+        let mut effects = ScreenEffects::new();
+        effects.colour_filter.matrix[0] = [0.5, 0.5, 0.0, 0.0];
+
+        assert!(!PostProcessUniform::compute(&effects).is_identity());
+    }
+
+    #[test]
+    fn colour_matrices_are_sent_column_major() {
+        let mut effects = ScreenEffects::new();
+        effects.colour_filter.matrix[0] = [0.5, 0.25, 0.0, 0.0];
+
+        let uniform = PostProcessUniform::compute(&effects);
+
+        assert_eq!(uniform.colour_matrix[0][0], 0.5);
+        assert_eq!(uniform.colour_matrix[1][0], 0.25);
+    }
+}
@@ -0,0 +1,94 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// How often the shaders directory is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches the shaders directory in the source tree, so shaders can be tweaked while the game is
+/// running instead of rebuilding it. Only used in debug builds.
+pub struct ShaderWatcher {
+    dir: PathBuf,
+
+    /// When each shader was last modified, keyed by file name.
+    modified: HashMap<String, SystemTime>,
+    last_poll: Instant,
+}
+
+impl ShaderWatcher {
+    /// Starts watching the shaders directory, if it exists, ex. when running from the source tree.
+    pub fn new() -> Option<Self> {
+        let dir = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/src/graphics/shaders"));
+
+        if !dir.is_dir() {
+            return None;
+        }
+
+        log::info!("Watching {} for shader changes", dir.display());
+
+        let mut watcher = Self {
+            dir,
+            modified: HashMap::new(),
+            last_poll: Instant::now(),
+        };
+
+        // Record when each shader was last modified, so only later changes are reloaded.
+        watcher.poll();
+        Some(watcher)
+    }
+
+    /// Gets the file name and new source of each shader that changed since the last check.
+    pub fn changed_shaders(&mut self) -> Vec<(String, String)> {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return Vec::new();
+        }
+
+        self.last_poll = Instant::now();
+        self.poll()
+    }
+
+    fn poll(&mut self) -> Vec<(String, String)> {
+        let mut changed = Vec::new();
+
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::warn!("Error reading {}: {}", self.dir.display(), e);
+                return changed;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+
+            if path.extension().is_none_or(|extension| extension != "wgsl") {
+                continue;
+            }
+
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+
+            let Ok(modified) = entry.metadata().and_then(|metadata| metadata.modified()) else {
+                continue;
+            };
+
+            let previous = self.modified.insert(file_name.to_string(), modified);
+
+            // New shaders aren't used by any pipelines yet, so they don't need to be reloaded.
+            if previous.is_none_or(|previous| previous == modified) {
+                continue;
+            }
+
+            match fs::read_to_string(&path) {
+                Ok(source) => changed.push((file_name.to_string(), source)),
+                Err(e) => log::error!("Error reading {}: {}", path.display(), e),
+            }
+        }
+
+        changed
+    }
+}
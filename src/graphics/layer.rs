@@ -0,0 +1,89 @@
+use super::SamplerPolicy;
+
+/// The layers the game is drawn in, from back to front.
+/// A map's tile and object layers, and backgrounds drawn in front of the world, go between these
+/// once maps are loaded.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RenderLayer {
+    /// Map backgrounds drawn behind everything else.
+    Background,
+
+    /// Characters, mobs, NPCs, drops, etc.
+    #[default]
+    World,
+
+    Ui,
+}
+
+impl RenderLayer {
+    /// Gets the `SamplerPolicy` textures in the layer are drawn with by default.
+    pub fn sampler_policy(self) -> SamplerPolicy {
+        match self {
+            // Backgrounds are often stretched to fill the screen, so they're smoothed.
+            RenderLayer::Background => SamplerPolicy::Linear,
+            _ => SamplerPolicy::Nearest,
+        }
+    }
+}
+
+/// The order an item is drawn in.
+/// Items are sorted by layer, then by their NX z value, then by the order they were added in, so
+/// overlapping items are always drawn the same way from frame to frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RenderOrder {
+    pub layer: RenderLayer,
+    pub z: i64,
+    pub insertion: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order(layer: RenderLayer, z: i64, insertion: usize) -> RenderOrder {
+        RenderOrder {
+            layer,
+            z,
+            insertion,
+        }
+    }
+
+    #[test]
+    fn items_are_sorted_by_layer_then_z_then_insertion() {
+        let mut orders = vec![
+            order(RenderLayer::Ui, 0, 0),
+            order(RenderLayer::World, 5, 1),
+            order(RenderLayer::Background, 10, 2),
+            order(RenderLayer::World, 5, 3),
+            order(RenderLayer::World, -1, 4),
+            order(RenderLayer::Background, 2, 5),
+        ];
+
+        orders.sort();
+
+        assert_eq!(
+            orders,
+            vec![
+                order(RenderLayer::Background, 2, 5),
+                order(RenderLayer::Background, 10, 2),
+                order(RenderLayer::World, -1, 4),
+                order(RenderLayer::World, 5, 1),
+                order(RenderLayer::World, 5, 3),
+                order(RenderLayer::Ui, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn items_added_first_are_drawn_first_when_everything_else_is_equal() {
+        let mut orders: Vec<RenderOrder> = (0..10)
+            .rev()
+            .map(|insertion| order(RenderLayer::World, 0, insertion))
+            .collect();
+
+        orders.sort();
+
+        let insertions: Vec<usize> = orders.iter().map(|order| order.insertion).collect();
+        assert_eq!(insertions, (0..10).collect::<Vec<_>>());
+    }
+}
@@ -1,31 +1,66 @@
 use ultraviolet::Mat4;
-use ultraviolet::Similarity3;
 use ultraviolet::Vec3;
 use ultraviolet::Vec4;
 use uuid::Uuid;
 
 use crate::component::Camera;
+use crate::component::Colour;
 use crate::component::Transform;
 
+pub use self::clip::ClipRect;
+pub use self::layer::RenderLayer;
+pub use self::layer::RenderOrder;
+pub use self::options::RendererOptions;
+pub use self::pipeline::BlendMode;
+pub use self::pipeline::PipelineKey;
+pub use self::pipeline::PipelineKind;
+pub use self::pipeline::Shader;
+pub use self::renderer::FrameAck;
 pub use self::renderer::RenderItem;
 pub use self::renderer::Renderer;
 pub use self::renderer::RendererEvent;
+pub use self::renderer_manager::extract_renderables;
+pub use self::renderer_manager::extract_shapes;
+pub use self::renderer_manager::Extractor;
 pub use self::renderer_manager::RendererManager;
+pub use self::sampler::SamplerPolicy;
+pub use self::shape::Shape;
 pub use self::sprite::Sprite;
+pub use self::stats::RenderStats;
+pub use self::texture::PixelFormat;
 pub use self::texture::Texture;
+pub use self::viewport::Viewport;
 
+mod atlas;
+mod clip;
+mod composite;
+#[cfg(debug_assertions)]
+mod hot_reload;
+mod layer;
+mod options;
+mod pipeline;
 mod renderer;
 mod renderer_manager;
+mod sampler;
+mod screenshot;
+mod shape;
 mod sprite;
+mod stats;
 mod texture;
 pub mod ui;
+mod viewport;
 
 pub trait Renderable {
+    /// The shader the `Renderable`'s render pipeline is built from.
+    const SHADER: Shader;
+
     fn create_render_pipeline(
         device: &wgpu::Device,
-        transform_bind_group_layout: &wgpu::BindGroupLayout,
+        shader: &wgpu::ShaderModule,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
         texture_bind_group_layout: &wgpu::BindGroupLayout,
-        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        blend_state: wgpu::BlendState,
     ) -> wgpu::RenderPipeline;
 }
 
@@ -38,8 +73,60 @@ pub trait RenderableV2 {
 
     /// Gets the `Renderable` component's `Transform`.
     fn transform(&self) -> &Transform;
+
+    /// Gets the `RenderLayer` the `Renderable` component is drawn in.
+    fn layer(&self) -> RenderLayer;
+
+    /// Gets the `Colour` the `Renderable` component's texture is multiplied with.
+    fn colour(&self) -> Colour;
+
+    /// Gets the `ClipRect` the `Renderable` component is clipped to, if any.
+    fn clip(&self) -> Option<ClipRect> {
+        None
+    }
+
+    /// Gets how far the `Renderable` component is through its texture's frame, from 0.0 to 1.0.
+    /// The texture's alpha is interpolated over its frame, see `Texture::alpha_at`.
+    fn frame_progress(&self) -> f32 {
+        0.0
+    }
+
+    /// Gets the `BlendMode` the `Renderable` component is drawn with.
+    fn blend_mode(&self) -> BlendMode {
+        BlendMode::Alpha
+    }
+
+    /// Gets the `SamplerPolicy` the `Renderable` component's texture is drawn with, which is the
+    /// texture's own policy if it has one, otherwise its layer's.
+    fn sampler_policy(&self) -> SamplerPolicy {
+        self.texture()
+            .sampler_policy
+            .unwrap_or_else(|| self.layer().sampler_policy())
+    }
 }
 
+/// The corners of a unit quad. Every item is drawn by scaling this with its instance's transform.
+pub(crate) const QUAD_VERTICES: &[Vertex] = &[
+    Vertex {
+        position: [0.0, 0.0, 0.0],
+        tex_coords: [0.0, 0.0],
+    },
+    Vertex {
+        position: [0.0, 1.0, 0.0],
+        tex_coords: [0.0, 1.0],
+    },
+    Vertex {
+        position: [1.0, 1.0, 0.0],
+        tex_coords: [1.0, 1.0],
+    },
+    Vertex {
+        position: [1.0, 0.0, 0.0],
+        tex_coords: [1.0, 0.0],
+    },
+];
+
+pub(crate) const QUAD_INDICES: &[u16] = &[0, 1, 3, 3, 1, 2];
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub(crate) struct Vertex {
@@ -70,34 +157,102 @@ impl Vertex {
     }
 }
 
+/// Per-item data for drawing a quad, shared by every item in an instanced draw call.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct Uniform {
+pub struct Instance {
     pub model_transform: [[f32; 4]; 4],
-    pub camera_view: [[f32; 4]; 4],
+
+    /// The area of the atlas page to sample, as (x, y, width, height) in texture coordinates.
+    pub uv_rect: [f32; 4],
+
+    /// The colour multiplied with the sampled texture.
+    pub tint: [f32; 4],
 }
 
-impl Uniform {
-    pub fn compute(texture: &Texture, transform: &Transform, camera: &Camera) -> Self {
-        let mut model_transform = Similarity3::identity();
-        model_transform.prepend_scaling(transform.scale);
+impl Instance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+        2 => Float32x4,
+        3 => Float32x4,
+        4 => Float32x4,
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+    ];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Instance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+
+    pub fn compute(
+        texture: &Texture,
+        transform: &Transform,
+        colour: Colour,
+        uv_rect: [f32; 4],
+        frame_progress: f32,
+    ) -> Self {
+        let (width, height) = texture.size;
+        let (origin_x, origin_y) = texture.origin.unwrap_or((0, 0));
+        let (scale_x, scale_y) = transform.scale;
+        let flip = if transform.flip_x { -1.0 } else { 1.0 };
 
-        if let Some((x, y)) = texture.origin {
-            model_transform.append_translation(Vec3 {
-                x: -x as f32,
-                y: -y as f32,
+        // Applied right to left: the quad is scaled up to the texture's size and moved so the
+        // origin is at (0, 0), then scaled, mirrored and rotated around the origin, then moved
+        // into position.
+        let model_transform = Mat4::from_translation(Vec3 {
+            x: transform.x,
+            y: transform.y,
+            z: transform.z,
+        }) * Mat4::from_rotation_z(transform.rotation)
+            * Mat4::from_nonuniform_scale(Vec3 {
+                x: scale_x * flip,
+                y: scale_y,
+                z: 1.0,
+            })
+            * Mat4::from_translation(Vec3 {
+                x: -origin_x as f32,
+                y: -origin_y as f32,
                 z: 0.0,
+            })
+            * Mat4::from_nonuniform_scale(Vec3 {
+                x: width,
+                y: height,
+                z: 1.0,
             });
+
+        // Fade the colour by the texture's own alpha.
+        let mut tint = colour.to_rgba_f32();
+        tint[3] *= texture.alpha_at(frame_progress);
+
+        Self {
+            model_transform: create_matrix4(&model_transform),
+            uv_rect,
+            tint,
         }
+    }
 
-        model_transform.append_translation(Vec3 {
-            x: transform.x * transform.scale,
-            y: transform.y * transform.scale,
-            z: transform.z,
-        });
+    /// Creates an instance for an untextured quad, filled with `colour`.
+    pub fn primitive(model_transform: &Mat4, colour: Colour) -> Self {
+        Self {
+            model_transform: create_matrix4(model_transform),
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            tint: colour.to_rgba_f32(),
+        }
+    }
+}
 
-        // TODO rotation?
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub camera_view: [[f32; 4]; 4],
+}
 
+impl CameraUniform {
+    pub fn compute(camera: &Camera) -> Self {
         let camera_view = ultraviolet::projection::lh_ydown::orthographic_wgpu_dx(
             camera.left,
             camera.right,
@@ -108,7 +263,6 @@ impl Uniform {
         );
 
         Self {
-            model_transform: create_matrix4(&model_transform.into_homogeneous_matrix()),
             camera_view: create_matrix4(&camera_view),
         }
     }
@@ -126,3 +280,202 @@ fn create_matrix4(t: &Mat4) -> [[f32; 4]; 4] {
 fn create_matrix(t: &Vec4) -> [f32; 4] {
     [t.x, t.y, t.z, t.w]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4x4 texture with no origin, which doesn't fade over its frame.
+    pub(super) fn test_texture() -> Texture {
+        Texture {
+            path: "test".to_string(),
+            width: 4,
+            height: 4,
+            data: vec![0; 4 * 4 * 4],
+            format: PixelFormat::Bgra8,
+            origin: None,
+            z: None,
+            alpha: (255, 255),
+            size: (4.0, 4.0),
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            sampler_policy: None,
+        }
+    }
+
+    pub(super) struct TestRenderable {
+        id: Uuid,
+        texture: Texture,
+        transform: Transform,
+        layer: RenderLayer,
+    }
+
+    impl TestRenderable {
+        pub(super) fn new(layer: RenderLayer, sampler_policy: Option<SamplerPolicy>) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                texture: Texture {
+                    sampler_policy,
+                    ..test_texture()
+                },
+                transform: Transform::default(),
+                layer,
+            }
+        }
+    }
+
+    impl RenderableV2 for TestRenderable {
+        fn id(&self) -> &Uuid {
+            &self.id
+        }
+
+        fn texture(&self) -> &Texture {
+            &self.texture
+        }
+
+        fn transform(&self) -> &Transform {
+            &self.transform
+        }
+
+        fn layer(&self) -> RenderLayer {
+            self.layer
+        }
+
+        fn colour(&self) -> Colour {
+            Colour::white()
+        }
+    }
+
+    #[test]
+    fn sampler_policy_defaults_to_the_layers() {
+        let background = TestRenderable::new(RenderLayer::Background, None);
+        let ui = TestRenderable::new(RenderLayer::Ui, None);
+
+        assert_eq!(background.sampler_policy(), SamplerPolicy::Linear);
+        assert_eq!(ui.sampler_policy(), SamplerPolicy::Nearest);
+    }
+
+    #[test]
+    fn sampler_policy_of_the_texture_overrides_the_layers() {
+        let background = TestRenderable::new(RenderLayer::Background, Some(SamplerPolicy::Nearest));
+
+        assert_eq!(background.sampler_policy(), SamplerPolicy::Nearest);
+    }
+
+    #[test]
+    fn instances_fade_with_their_texture_over_its_frame() {
+        let texture = Texture {
+            alpha: (255, 0),
+            ..test_texture()
+        };
+        let compute = |frame_progress| {
+            Instance::compute(
+                &texture,
+                &Transform::default(),
+                Colour::white(),
+                texture.uv_rect,
+                frame_progress,
+            )
+        };
+
+        assert_eq!(compute(0.0).tint[3], 1.0);
+        assert_eq!(compute(0.5).tint[3], 0.5);
+        assert_eq!(compute(1.0).tint[3], 0.0);
+    }
+
+    /// Gets where the corners of an instance's quad end up, clockwise from the top left.
+    fn corners(instance: &Instance) -> [(f32, f32); 4] {
+        let model_transform = Mat4::from(instance.model_transform);
+
+        [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)].map(|(x, y)| {
+            let corner = model_transform.transform_point3(Vec3::new(x, y, 0.0));
+            (corner.x, corner.y)
+        })
+    }
+
+    fn assert_corners(transform: Transform, expected: [(f32, f32); 4]) {
+        // A 4x2 texture with its origin at the bottom of its second column.
+        let texture = Texture {
+            origin: Some((1, 2)),
+            size: (4.0, 2.0),
+            ..test_texture()
+        };
+        let instance =
+            Instance::compute(&texture, &transform, Colour::white(), texture.uv_rect, 0.0);
+
+        for (corner, expected) in corners(&instance).into_iter().zip(expected) {
+            assert!(
+                (corner.0 - expected.0).abs() < 1e-4 && (corner.1 - expected.1).abs() < 1e-4,
+                "expected {:?}, got {:?}",
+                expected,
+                corners(&instance),
+            );
+        }
+    }
+
+    fn at_origin() -> Transform {
+        Transform::from_xyz(10.0, 20.0, 0.0)
+    }
+
+    #[test]
+    fn origins_are_placed_at_the_position() {
+        assert_corners(
+            at_origin(),
+            [(9.0, 18.0), (13.0, 18.0), (13.0, 20.0), (9.0, 20.0)],
+        );
+    }
+
+    #[test]
+    fn flipping_mirrors_around_the_origin() {
+        let transform = Transform {
+            flip_x: true,
+            ..at_origin()
+        };
+
+        assert_corners(
+            transform,
+            [(11.0, 18.0), (7.0, 18.0), (7.0, 20.0), (11.0, 20.0)],
+        );
+    }
+
+    #[test]
+    fn rotating_turns_clockwise_around_the_origin() {
+        let transform = Transform {
+            rotation: std::f32::consts::FRAC_PI_2,
+            ..at_origin()
+        };
+
+        assert_corners(
+            transform,
+            [(12.0, 19.0), (12.0, 23.0), (10.0, 23.0), (10.0, 19.0)],
+        );
+    }
+
+    #[test]
+    fn scaling_stretches_away_from_the_origin() {
+        let transform = Transform {
+            scale: (2.0, 3.0),
+            ..at_origin()
+        };
+
+        assert_corners(
+            transform,
+            [(8.0, 14.0), (16.0, 14.0), (16.0, 20.0), (8.0, 20.0)],
+        );
+    }
+
+    #[test]
+    fn transforms_combine_around_the_origin() {
+        // Scaled and mirrored first, then rotated, so the stretched width ends up vertical.
+        let transform = Transform {
+            scale: (2.0, 3.0),
+            rotation: std::f32::consts::FRAC_PI_2,
+            flip_x: true,
+            ..at_origin()
+        };
+
+        assert_corners(
+            transform,
+            [(16.0, 22.0), (16.0, 14.0), (10.0, 14.0), (10.0, 22.0)],
+        );
+    }
+}
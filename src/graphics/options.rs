@@ -0,0 +1,123 @@
+use std::str::FromStr;
+
+use crate::settings::Settings;
+
+use super::Viewport;
+
+/// Options for picking the GPU and presenting frames.
+#[derive(Copy, Clone, Debug)]
+pub struct RendererOptions {
+    pub power_preference: PowerPreference,
+
+    /// Use a software adapter, ex. on machines without a GPU.
+    pub force_fallback_adapter: bool,
+    pub vsync: Vsync,
+
+    /// The number of frames the GPU can be working on before presenting blocks, and the number of
+    /// frames the game thread can send before the renderer acknowledges them.
+    pub max_frames_in_flight: u32,
+
+    pub viewport: Viewport,
+}
+
+impl RendererOptions {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let default = Self::default();
+
+        Self {
+            power_preference: settings.get_or("power_preference", default.power_preference),
+            force_fallback_adapter: settings
+                .get_or("force_fallback_adapter", default.force_fallback_adapter),
+            vsync: settings.get_or("vsync", default.vsync),
+            max_frames_in_flight: settings
+                .get_or("max_frames_in_flight", default.max_frames_in_flight)
+                .max(1),
+            viewport: Viewport::from_settings(settings),
+        }
+    }
+}
+
+impl Default for RendererOptions {
+    fn default() -> Self {
+        Self {
+            power_preference: PowerPreference::High,
+            force_fallback_adapter: false,
+            vsync: Vsync::On,
+            max_frames_in_flight: 2,
+            viewport: Viewport::default(),
+        }
+    }
+}
+
+/// Which GPU to prefer when a machine has more than one, ex. a laptop's integrated GPU.
+#[derive(Copy, Clone, Debug)]
+pub enum PowerPreference {
+    None,
+    Low,
+    High,
+}
+
+impl PowerPreference {
+    pub fn to_wgpu(self) -> wgpu::PowerPreference {
+        match self {
+            PowerPreference::None => wgpu::PowerPreference::None,
+            PowerPreference::Low => wgpu::PowerPreference::LowPower,
+            PowerPreference::High => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+impl FromStr for PowerPreference {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(PowerPreference::None),
+            "low" => Ok(PowerPreference::Low),
+            "high" => Ok(PowerPreference::High),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Vsync {
+    /// Present frames as soon as they're ready, which can tear.
+    Off,
+
+    /// Wait for the display to refresh before presenting.
+    On,
+
+    /// Like `On`, but newer frames replace queued ones instead of waiting behind them.
+    Mailbox,
+}
+
+impl Vsync {
+    /// Picks a present mode the surface supports, falling back to regular vsync if needed.
+    pub fn present_mode(self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        match self {
+            Vsync::Off => wgpu::PresentMode::AutoNoVsync,
+            Vsync::On => wgpu::PresentMode::Fifo,
+            Vsync::Mailbox if supported.contains(&wgpu::PresentMode::Mailbox) => {
+                wgpu::PresentMode::Mailbox
+            }
+            Vsync::Mailbox => {
+                log::warn!("Mailbox present mode isn't supported, using vsync instead");
+                wgpu::PresentMode::Fifo
+            }
+        }
+    }
+}
+
+impl FromStr for Vsync {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" | "false" => Ok(Vsync::Off),
+            "on" | "true" => Ok(Vsync::On),
+            "mailbox" => Ok(Vsync::Mailbox),
+            _ => Err(()),
+        }
+    }
+}
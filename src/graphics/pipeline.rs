@@ -0,0 +1,128 @@
+/// How an item's colour is combined with what's already been drawn behind it.
+///
+/// Shaders output premultiplied colours, so blending only scales the background by the item's
+/// alpha. Other modes, ex. additive for glows and skill effects, belong here once something is
+/// drawn with them.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// Regular "over" blending.
+    #[default]
+    Alpha,
+}
+
+impl BlendMode {
+    pub fn blend_state(self) -> wgpu::BlendState {
+        let colour = match self {
+            BlendMode::Alpha => wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+
+        wgpu::BlendState {
+            color: colour,
+            alpha: wgpu::BlendComponent::OVER,
+        }
+    }
+}
+
+/// The kinds of render pipelines items can be drawn with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PipelineKind {
+    Texture,
+
+    /// Flat coloured shapes, see `Shape`.
+    Primitive,
+}
+
+/// Identifies a variant of a render pipeline. The renderer builds one pipeline per key, the first
+/// time an item needs it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    pub kind: PipelineKind,
+    pub blend_mode: BlendMode,
+}
+
+/// A WGSL shader from the `shaders` directory, compiled into the game.
+#[derive(Copy, Clone, Debug)]
+pub struct Shader {
+    /// The shader's file name in the `shaders` directory, used to find it when hot reloading.
+    pub file_name: &'static str,
+    pub source: &'static str,
+}
+
+impl Shader {
+    pub fn create_module(self, device: &wgpu::Device) -> wgpu::ShaderModule {
+        create_shader_module(device, self.file_name, self.source)
+    }
+}
+
+pub fn create_shader_module(
+    device: &wgpu::Device,
+    file_name: &str,
+    source: &str,
+) -> wgpu::ShaderModule {
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(file_name),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Blends a premultiplied `src` colour over `dst` the way the GPU would with `state`.
+    fn blend(state: wgpu::BlendState, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] {
+        let factor = |factor: wgpu::BlendFactor| match factor {
+            wgpu::BlendFactor::Zero => 0.0,
+            wgpu::BlendFactor::One => 1.0,
+            wgpu::BlendFactor::OneMinusSrcAlpha => 1.0 - src[3],
+            factor => unimplemented!("{:?}", factor),
+        };
+
+        let component = |component: wgpu::BlendComponent, src: f32, dst: f32| {
+            assert_eq!(component.operation, wgpu::BlendOperation::Add);
+            src * factor(component.src_factor) + dst * factor(component.dst_factor)
+        };
+
+        [
+            component(state.color, src[0], dst[0]),
+            component(state.color, src[1], dst[1]),
+            component(state.color, src[2], dst[2]),
+            component(state.alpha, src[3], dst[3]),
+        ]
+    }
+
+    #[test]
+    fn alpha_blending_covers_the_background_with_opaque_items() {
+        let state = BlendMode::Alpha.blend_state();
+
+        assert_eq!(
+            blend(state, [1.0, 0.0, 0.0, 1.0], [0.0, 0.0, 1.0, 1.0]),
+            [1.0, 0.0, 0.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn alpha_blending_leaves_the_background_under_transparent_items() {
+        let state = BlendMode::Alpha.blend_state();
+
+        assert_eq!(
+            blend(state, [0.0; 4], [0.0, 0.0, 1.0, 1.0]),
+            [0.0, 0.0, 1.0, 1.0]
+        );
+    }
+
+    #[test]
+    fn alpha_blending_expects_premultiplied_colours() {
+        let state = BlendMode::Alpha.blend_state();
+
+        // Half transparent red, premultiplied, over opaque blue.
+        assert_eq!(
+            blend(state, [0.5, 0.0, 0.0, 0.5], [0.0, 0.0, 1.0, 1.0]),
+            [0.5, 0.0, 0.5, 1.0]
+        );
+    }
+}
@@ -1,39 +1,105 @@
 use std::{
     collections::HashMap,
-    iter,
+    fmt, iter,
     ops::Range,
     sync::{mpsc, Arc},
+    thread,
+    time::Instant,
 };
 
-use uuid::Uuid;
 use wgpu::util::DeviceExt;
 use winit::{dpi::PhysicalSize, window::Window};
 
-use super::{Renderable, Texture, Uniform};
+use super::{
+    atlas::AtlasRegion,
+    composite::{Compositor, PostProcessUniform},
+    options::RendererOptions,
+    sampler::SamplerCache,
+    screenshot::save_screenshot,
+    stats::{GpuTimer, RenderStats},
+    viewport::{ScalingMode, Viewport},
+    CameraUniform, ClipRect, Instance, PipelineKey, PipelineKind, PixelFormat, RenderOrder,
+    Renderable, SamplerPolicy, Shape, Texture, QUAD_INDICES, QUAD_VERTICES,
+};
+
+#[cfg(debug_assertions)]
+use super::{hot_reload::ShaderWatcher, pipeline::create_shader_module, Shader};
+
+/// The number of instances the instance buffer initially has room for.
+const INITIAL_INSTANCE_CAPACITY: usize = 1024;
+
+/// Builds a render pipeline, see `Renderable::create_render_pipeline`.
+type PipelineBuilder = fn(
+    &wgpu::Device,
+    &wgpu::ShaderModule,
+    &wgpu::BindGroupLayout,
+    &wgpu::BindGroupLayout,
+    wgpu::TextureFormat,
+    wgpu::BlendState,
+) -> wgpu::RenderPipeline;
 
 pub struct Renderer {
-    window: Arc<Window>,
     receiver: mpsc::Receiver<RendererEvent>,
+    ack_sender: mpsc::Sender<FrameAck>,
+
+    // Frames whose updates haven't been submitted yet. They're acknowledged after the next submit.
+    pending_acks: Vec<FrameAck>,
 
-    surface: wgpu::Surface<'static>,
+    target: RenderTarget,
+    viewport: Viewport,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
 
-    transform_bind_group_layout: wgpu::BindGroupLayout,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
     texture_bind_group_layout: wgpu::BindGroupLayout,
 
-    // A map of `Renderable` type names to render pipelines.
-    render_pipelines: HashMap<String, wgpu::RenderPipeline>,
-    vertex_buffers: HashMap<Uuid, wgpu::Buffer>,
-    index_buffers: HashMap<Uuid, wgpu::Buffer>,
+    // How to build each kind of render pipeline.
+    pipeline_kinds: HashMap<PipelineKind, RegisteredPipeline>,
+
+    // Render pipelines that have been built so far, one per kind and blend mode.
+    render_pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+
+    // Every item is an instance of the same quad.
+    quad_vertex_buffer: wgpu::Buffer,
+    quad_index_buffer: wgpu::Buffer,
+
+    // Per-item data for the current frame, grown when a frame has more items than it fits.
+    instance_buffer: wgpu::Buffer,
+    instance_capacity: usize,
+
+    // The instances currently in the instance buffer, so unchanged instances aren't re-uploaded.
+    uploaded_instances: Vec<Instance>,
+
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    atlas_pages: HashMap<usize, AtlasPageTexture>,
+
+    // Samplers shared by every atlas page, one per policy.
+    samplers: SamplerCache,
+
+    // Applies screen effects, ex. fades, to the finished scene.
+    compositor: Compositor,
 
-    transform_bind_groups: HashMap<Uuid, (wgpu::BindGroup)>,
-    texture_bind_groups: HashMap<String, (wgpu::BindGroup, wgpu::Texture)>,
+    // Whether the next frame should be saved as a screenshot.
+    screenshot_requested: bool,
+
+    // Statistics for the frame being rendered, published to `stats_sender` once it's done.
+    stats: RenderStats,
+    stats_sender: Option<mpsc::Sender<RenderStats>>,
+    gpu_timer: Option<GpuTimer>,
+
+    // Reloads shaders when they're changed on disk, in debug builds.
+    #[cfg(debug_assertions)]
+    shader_watcher: Option<ShaderWatcher>,
 }
 
 impl Renderer {
-    pub async fn new(window: Arc<Window>, receiver: mpsc::Receiver<RendererEvent>) -> Self {
+    pub async fn new(
+        window: Arc<Window>,
+        receiver: mpsc::Receiver<RendererEvent>,
+        ack_sender: mpsc::Sender<FrameAck>,
+        options: &RendererOptions,
+    ) -> Self {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
 
         let window_size = window.inner_size();
@@ -43,33 +109,100 @@ impl Renderer {
 
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::HighPerformance,
+                power_preference: options.power_preference.to_wgpu(),
                 compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
+                force_fallback_adapter: options.force_fallback_adapter,
             })
             .await
             .expect("adapter should be created");
 
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    required_features: wgpu::Features::empty(),
-                    required_limits: wgpu::Limits::default(),
-                    memory_hints: wgpu::MemoryHints::Performance,
-                },
-                None,
-            )
-            .await
-            .expect("device and queue should be created");
+        log::info!("Using adapter {:?}", adapter.get_info());
+
+        let (device, queue) = request_device(&adapter).await;
 
-        let config = surface
+        let mut config = surface
             .get_default_config(&adapter, window_size.width, window_size.height)
             .expect("surface configuration should be created");
 
+        let capabilities = surface.get_capabilities(&adapter);
+        config.present_mode = options.vsync.present_mode(&capabilities.present_modes);
+
+        // Frames have to be copied back from the surface to take screenshots.
+        if capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+            config.usage |= wgpu::TextureUsages::COPY_SRC;
+        }
+        config.desired_maximum_frame_latency = options.max_frames_in_flight;
+
         surface.configure(&device, &config);
 
-        let transform_bind_group_layout =
+        let target = RenderTarget::Window {
+            window,
+            surface,
+            config,
+        };
+
+        Self::with_target(
+            target,
+            options.viewport,
+            device,
+            queue,
+            receiver,
+            ack_sender,
+        )
+    }
+
+    /// Creates a renderer that draws into an offscreen texture instead of a window surface.
+    /// This doesn't need a display, so it can be used in CI to render scenes and read back the
+    /// result with `read_frame`.
+    pub async fn new_headless(
+        width: u32,
+        height: u32,
+        receiver: mpsc::Receiver<RendererEvent>,
+        ack_sender: mpsc::Sender<FrameAck>,
+    ) -> Self {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+
+        // Prefer a software adapter so the output doesn't depend on the machine's GPU, but don't
+        // fail if there isn't one available.
+        let adapter = match instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: true,
+            })
+            .await
+        {
+            Some(adapter) => adapter,
+            None => {
+                log::warn!("No fallback adapter found, using the default adapter instead");
+                instance
+                    .request_adapter(&wgpu::RequestAdapterOptions::default())
+                    .await
+                    .expect("adapter should be created")
+            }
+        };
+
+        let (device, queue) = request_device(&adapter).await;
+
+        let target = RenderTarget::Offscreen {
+            texture: create_offscreen_texture(&device, width, height),
+        };
+
+        // Draw at the texture's size, so frames aren't scaled.
+        let viewport = Viewport::new(width, height, ScalingMode::Integer);
+
+        Self::with_target(target, viewport, device, queue, receiver, ack_sender)
+    }
+
+    fn with_target(
+        target: RenderTarget,
+        viewport: Viewport,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        receiver: mpsc::Receiver<RendererEvent>,
+        ack_sender: mpsc::Sender<FrameAck>,
+    ) -> Self {
+        let camera_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
@@ -107,127 +240,301 @@ impl Renderer {
                 label: None,
             });
 
-        Self {
-            window,
+        let quad_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("quad vertex buffer"),
+            contents: bytemuck::cast_slice(QUAD_VERTICES),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let quad_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("quad index buffer"),
+            contents: bytemuck::cast_slice(QUAD_INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let instance_buffer = create_instance_buffer(&device, INITIAL_INSTANCE_CAPACITY);
+
+        let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("camera buffer"),
+            size: std::mem::size_of::<CameraUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("camera bind group"),
+        });
+
+        let format = match &target {
+            RenderTarget::Window { config, .. } => config.format,
+            RenderTarget::Offscreen { texture } => texture.format(),
+        };
+
+        let compositor = Compositor::new(&device, format, viewport.scaling.filter_mode());
+        let gpu_timer = GpuTimer::new(&device, &queue);
+
+        let mut renderer = Self {
             receiver,
-            surface,
+            ack_sender,
+            pending_acks: Vec::new(),
+            target,
+            viewport,
             device,
             queue,
-            config,
-            transform_bind_group_layout,
+            camera_bind_group_layout,
             texture_bind_group_layout,
+            pipeline_kinds: HashMap::new(),
             render_pipelines: HashMap::new(),
-            vertex_buffers: HashMap::new(),
-            index_buffers: HashMap::new(),
-            transform_bind_groups: HashMap::new(),
-            texture_bind_groups: HashMap::new(),
-        }
-    }
-
-    pub fn run(mut self) {
-        self.register_render_pipeline::<Texture>();
-
-        loop {
-            if let Ok(event) = self.receiver.recv() {
-                match event {
-                    RendererEvent::Render(updates, items) => {
-                        self.process_updates(updates);
-
-                        match self.render(items) {
-                            Ok(_) => {}
-                            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                                self.resize(self.window.inner_size());
-                            }
-                            Err(wgpu::SurfaceError::OutOfMemory) => {
-                                log::error!("System is out of memory, exiting");
-                                // TODO: event_loop.exit();
-                                // can probably use a one-shot channel for this.
-                            }
-                            Err(wgpu::SurfaceError::Timeout) => {
-                                log::warn!("Frame took longer than expected to render");
-                            }
-                        }
-                    }
-                    RendererEvent::Resize(new_size) => self.resize(new_size),
+            quad_vertex_buffer,
+            quad_index_buffer,
+            instance_buffer,
+            instance_capacity: INITIAL_INSTANCE_CAPACITY,
+            uploaded_instances: Vec::new(),
+            camera_buffer,
+            camera_bind_group,
+            atlas_pages: HashMap::new(),
+            samplers: SamplerCache::new(),
+            compositor,
+            screenshot_requested: false,
+            stats: RenderStats::default(),
+            stats_sender: None,
+            gpu_timer,
+            #[cfg(debug_assertions)]
+            shader_watcher: ShaderWatcher::new(),
+        };
+
+        renderer.register_render_pipeline::<Texture>(PipelineKind::Texture);
+        renderer.register_render_pipeline::<Shape>(PipelineKind::Primitive);
+        renderer
+    }
+
+    /// Publishes each frame's `RenderStats` to `sender`.
+    pub fn with_stats_sender(mut self, sender: mpsc::Sender<RenderStats>) -> Self {
+        self.stats_sender = Some(sender);
+        self
+    }
+
+    /// Handles events until every sender has been dropped, or an error the renderer can't recover
+    /// from occurs.
+    pub fn run(mut self) -> Result<(), RendererError> {
+        while let Ok(event) = self.receiver.recv() {
+            // Grab everything else that queued up while the last frame was being rendered.
+            let events = iter::once(event).chain(self.receiver.try_iter()).collect();
+            self.handle_events(events)?;
+        }
+
+        Ok(())
+    }
+
+    /// Handles every event currently in the channel without blocking.
+    /// This is how a headless renderer is driven, since it doesn't run on its own thread.
+    pub fn process_pending_events(&mut self) -> Result<(), RendererError> {
+        let events = self.receiver.try_iter().collect();
+        self.handle_events(events)
+    }
+
+    fn handle_events(&mut self, events: Vec<RendererEvent>) -> Result<(), RendererError> {
+        // If the renderer has fallen behind, only the newest frame is drawn. Older frames still
+        // have their updates applied, since later frames depend on them.
+        let newest_frame = events
+            .iter()
+            .rposition(|event| matches!(event, RendererEvent::Render(_)));
+
+        for (i, event) in events.into_iter().enumerate() {
+            match event {
+                RendererEvent::Render(frame) => {
+                    self.process_frame(frame, Some(i) == newest_frame)?;
                 }
+                RendererEvent::Resize(new_size) => self.resize(new_size),
+                RendererEvent::Screenshot => self.screenshot_requested = true,
             }
         }
+
+        Ok(())
     }
 
-    fn process_updates(&mut self, mut updates: Vec<RenderUpdate>) {
-        while let Some(update) = updates.pop() {
-            match update {
-                RenderUpdate::CreateTextureBindGroup {
-                    path,
-                    width,
-                    height,
-                    data,
-                } => {
-                    self.register_texture(path, width, height, data);
-                }
-                RenderUpdate::CreateIndexBuffer { id, data } => {
-                    self.index_buffers.insert(
-                        id,
-                        self.device
-                            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                                label: None,
-                                contents: &data,
-                                usage: wgpu::BufferUsages::INDEX,
-                            }),
-                    );
-                }
-                RenderUpdate::CreateVertexBuffer { id, data } => {
-                    self.vertex_buffers.insert(
-                        id,
-                        self.device
-                            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                                label: None,
-                                contents: &data,
-                                usage: wgpu::BufferUsages::VERTEX,
-                            }),
-                    );
+    fn process_frame(&mut self, frame: Frame, draw: bool) -> Result<(), RendererError> {
+        let mut items = Vec::new();
+
+        // Commands have to be processed in order, ex. a page has to exist before uploading to it.
+        let update_start = Instant::now();
+
+        for command in frame.commands {
+            match command {
+                RenderCommand::Update(update) => self.process_update(update),
+                RenderCommand::Draw(item) => items.push(item),
+            }
+        }
+
+        self.stats.update_time += update_start.elapsed();
+
+        // Skipped frames' updates are submitted along with the next frame that's drawn.
+        self.pending_acks.push(FrameAck {
+            frame: frame.id,
+            drawn: draw,
+        });
+
+        if !draw {
+            return Ok(());
+        }
+
+        #[cfg(debug_assertions)]
+        self.reload_shaders();
+
+        let render_start = Instant::now();
+        let result = self.render(items);
+        self.stats.render_time = render_start.elapsed();
+
+        // Nothing was submitted, but the updates still have to reach the GPU before they can be
+        // acknowledged.
+        if result.is_err() {
+            self.queue.submit(iter::empty());
+            self.send_acks();
+        }
+
+        self.publish_stats();
+
+        match result {
+            Ok(_) => {}
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.resize(self.target_size());
+            }
+            Err(wgpu::SurfaceError::OutOfMemory) => {
+                return Err(RendererError::OutOfMemory);
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                log::warn!("Frame took longer than expected to render");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lets the manager know the updates of every frame processed so far have been submitted to
+    /// the GPU.
+    fn send_acks(&mut self) {
+        for ack in self.pending_acks.drain(..) {
+            if self.ack_sender.send(ack).is_err() {
+                log::warn!("Nothing is listening for frame acknowledgements");
+                break;
+            }
+        }
+    }
+
+    /// Publishes the stats of the frame that was just drawn, including any skipped frames before
+    /// it, and starts counting again.
+    fn publish_stats(&mut self) {
+        if let Some(sender) = &self.stats_sender {
+            // Stop publishing once nothing is listening, ex. the game thread has exited.
+            if sender.send(self.stats).is_err() {
+                self.stats_sender = None;
+            }
+        }
+
+        // Keep showing the last GPU time until a newer one has been read back.
+        self.stats = RenderStats {
+            gpu_time: self.stats.gpu_time,
+            ..Default::default()
+        };
+    }
+
+    fn process_update(&mut self, update: RenderUpdate) {
+        match update {
+            RenderUpdate::CreateAtlasPage { page, size, format } => {
+                self.create_atlas_page(page, size, format);
+            }
+            RenderUpdate::UploadAtlasRegion { region, data } => {
+                self.upload_atlas_region(region, data);
+                self.stats.texture_uploads += 1;
+            }
+            RenderUpdate::ReleaseAtlasRegion { region } => {
+                // Clear the region, so whatever reuses its space doesn't pick up old pixels.
+                let data = vec![0; (4 * region.width * region.height) as usize];
+                self.upload_atlas_region(region, data);
+                self.stats.texture_uploads += 1;
+            }
+            RenderUpdate::DestroyAtlasPage { page } => {
+                if let Some(page) = self.atlas_pages.remove(&page) {
+                    page.texture.destroy();
                 }
-                RenderUpdate::UpdateTransformUniform { id, uniform } => {
-                    let uniform_buffer =
-                        self.device
-                            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                                label: None,
-                                contents: bytemuck::cast_slice(&[uniform]),
-                                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-                            });
-
-                    let uniform_bind_group =
-                        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-                            layout: &self.transform_bind_group_layout,
-                            entries: &[wgpu::BindGroupEntry {
-                                binding: 0,
-                                resource: uniform_buffer.as_entire_binding(),
-                            }],
-                            label: Some("uniform_bind_group"),
-                        });
-
-                    self.transform_bind_groups.insert(id, uniform_bind_group);
-                    self.queue
-                        .write_buffer(&uniform_buffer, 0, bytemuck::cast_slice(&[uniform]));
+            }
+            RenderUpdate::UpdateCamera(uniform) => {
+                self.queue
+                    .write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[uniform]));
+                self.stats.buffer_uploads += 1;
+            }
+            RenderUpdate::UpdatePostProcess(uniform) => {
+                if self.compositor.update(&self.queue, uniform) {
+                    self.stats.buffer_uploads += 1;
                 }
             }
         }
     }
 
-    fn render(&mut self, mut items: Vec<RenderItem>) -> Result<(), wgpu::SurfaceError> {
-        let frame = self.surface.get_current_texture()?;
-        let view = frame
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    fn render(&mut self, items: Vec<RenderItem>) -> Result<(), wgpu::SurfaceError> {
+        // The surface texture is only kept around so it can be presented after submitting.
+        let (frame, view) = match &self.target {
+            RenderTarget::Window { surface, .. } => {
+                let frame = surface.get_current_texture()?;
+                let view = frame
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(frame), view)
+            }
+            RenderTarget::Offscreen { texture } => (
+                None,
+                texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            ),
+        };
+
+        if let Some(gpu_time) = self
+            .gpu_timer
+            .as_mut()
+            .and_then(|timer| timer.try_read(&self.device))
+        {
+            self.stats.gpu_time = Some(gpu_time);
+        }
+
+        let instances: Vec<Instance> = items.iter().map(|item| item.instance).collect();
+        self.write_instances(&instances);
+
+        let batches = get_batches(&items);
+
+        for batch in batches.iter() {
+            self.create_render_pipeline(batch.pipeline);
+
+            if let Some(page) = batch.atlas_page {
+                self.create_page_bind_group(page, batch.sampler);
+            }
+        }
+
+        let frame_size = self.frame_size();
+        let scene_size = self.viewport.virtual_size;
+
+        // The scene is drawn at the virtual resolution, so it's drawn offscreen and composited
+        // afterwards if it has to be scaled to fit the frame or have effects applied.
+        let scene_view = if frame_size != scene_size || self.compositor.has_effects() {
+            Some(self.compositor.scene_view(&self.device, scene_size))
+        } else {
+            None
+        };
 
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
+        // Frames aren't timed while the last timed frame is still being read back.
+        let timer = self.gpu_timer.as_ref().filter(|timer| timer.is_ready());
+
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &view,
+                view: scene_view.as_ref().unwrap_or(&view),
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load: wgpu::LoadOp::Clear(wgpu::Color {
@@ -241,165 +548,797 @@ impl Renderer {
             })],
             depth_stencil_attachment: None,
             occlusion_query_set: None,
-            timestamp_writes: None,
+            timestamp_writes: timer.map(|timer| timer.timestamp_writes(true, scene_view.is_none())),
         });
 
-        // Process each render item one by one.
-        while let Some(item) = items.pop() {
-            // Set the pipeline for the item type.
-            let render_pipeline = self.render_pipelines.get(&item.type_name).unwrap();
-            render_pass.set_pipeline(render_pipeline);
+        render_pass.set_vertex_buffer(0, self.quad_vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.set_index_buffer(self.quad_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        self.stats.bind_group_switches += 1;
+
+        let mut current_pipeline = None;
+        let mut current_page = None;
 
-            // Set the vertex buffer for the item's entity.
-            let vertex_buffer = self.vertex_buffers.get(&item.id).unwrap();
-            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        // Draw consecutive items sharing a pipeline, atlas page, sampler and clip rect in one draw
+        // call.
+        for batch in batches {
+            let [x, y, width, height] = match batch.clip {
+                Some(clip) => clip.to_scissor_rect(scene_size),
+                None => [0, 0, scene_size.width, scene_size.height],
+            };
 
-            // Set the index buffer for the item's entity.
-            let index_buffer = self.index_buffers.get(&item.id).unwrap();
-            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            // The clip rect is entirely outside of the scene, so there's nothing to draw.
+            if width == 0 || height == 0 {
+                continue;
+            }
+
+            render_pass.set_scissor_rect(x, y, width, height);
+
+            // Batches are split on samplers and clip rects too, so the pipeline and page don't
+            // always change.
+            if current_pipeline != Some(batch.pipeline) {
+                let render_pipeline = self.render_pipelines.get(&batch.pipeline).unwrap();
+                render_pass.set_pipeline(render_pipeline);
+
+                current_pipeline = Some(batch.pipeline);
+                self.stats.pipeline_switches += 1;
+            }
+
+            // Set the bind group for the atlas page containing the batch's textures (if applicable).
+            let page = batch.atlas_page.map(|page| (page, batch.sampler));
 
-            let transform_bind_group = self.transform_bind_groups.get(&item.id).unwrap();
-            render_pass.set_bind_group(0, transform_bind_group, &[]);
+            if let Some((index, sampler)) = page.filter(|_| current_page != page) {
+                let page_bind_group = &self.atlas_pages.get(&index).unwrap().bind_groups[&sampler];
+                render_pass.set_bind_group(1, page_bind_group, &[]);
 
-            // Set the bind group for the item's texture (if applicable).
-            if let Some(texture_name) = item.texture_name {
-                let texture_bind_group = self.texture_bind_groups.get(&texture_name).unwrap();
-                render_pass.set_bind_group(1, &texture_bind_group.0, &[]);
+                current_page = page;
+                self.stats.bind_group_switches += 1;
             }
 
-            render_pass.draw_indexed(item.range, 0, 0..1);
+            render_pass.draw_indexed(0..QUAD_INDICES.len() as u32, 0, batch.instances);
+            self.stats.draw_calls += 1;
         }
 
         drop(render_pass);
+
+        if scene_view.is_some() {
+            let rect = self.viewport.frame_rect(frame_size);
+            let timestamp_writes = timer.map(|timer| timer.timestamp_writes(false, true));
+            self.compositor
+                .draw(&mut encoder, &view, rect, timestamp_writes);
+
+            self.stats.draw_calls += 1;
+            self.stats.pipeline_switches += 1;
+            self.stats.bind_group_switches += 1;
+        }
+
+        let timed = timer.is_some();
+
+        if let Some(timer) = timer {
+            timer.resolve(&mut encoder);
+        }
+
         self.queue.submit(iter::once(encoder.finish()));
-        frame.present();
+        self.send_acks();
+
+        if timed {
+            self.gpu_timer.as_mut().unwrap().start_readback();
+        }
+
+        // The frame has to be copied before it's presented, since the surface texture is gone
+        // afterwards.
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+
+            match (&frame, &self.target) {
+                (Some(frame), _) => self.take_screenshot(&frame.texture),
+                (None, RenderTarget::Offscreen { texture }) => self.take_screenshot(texture),
+                (None, RenderTarget::Window { .. }) => {}
+            }
+        }
+
+        if let Some(frame) = frame {
+            frame.present();
+        }
 
         Ok(())
     }
 
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
-        if new_size.width > 0 && new_size.height > 0 {
-            self.config.width = new_size.width;
-            self.config.height = new_size.height;
+        if new_size.width == 0 || new_size.height == 0 {
+            return;
+        }
+
+        match &mut self.target {
+            RenderTarget::Window {
+                surface, config, ..
+            } => {
+                config.width = new_size.width;
+                config.height = new_size.height;
+
+                // FIXME: this only works on the main thread
+                surface.configure(&self.device, config);
+            }
+            RenderTarget::Offscreen { texture } => {
+                *texture = create_offscreen_texture(&self.device, new_size.width, new_size.height);
+            }
+        }
+    }
+
+    /// Copies a frame back from the GPU and saves it as a screenshot in the background.
+    fn take_screenshot(&self, texture: &wgpu::Texture) {
+        if !texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            log::warn!("Frames can't be copied on this surface, screenshot skipped");
+            return;
+        }
+
+        let image = read_texture(&self.device, &self.queue, texture);
+
+        // Encoding the PNG is slow, so don't hold up the next frame for it.
+        thread::spawn(move || match save_screenshot(&image) {
+            Ok(path) => log::info!("Saved screenshot to {}", path.display()),
+            Err(e) => log::error!("Error saving screenshot: {}", e),
+        });
+    }
+
+    /// Copies the last rendered frame back from the GPU as an RGBA image.
+    /// Returns `None` for window targets, since the surface texture is gone once presented.
+    pub fn read_frame(&self) -> Option<image::RgbaImage> {
+        match &self.target {
+            RenderTarget::Window { .. } => None,
+            RenderTarget::Offscreen { texture } => {
+                Some(read_texture(&self.device, &self.queue, texture))
+            }
+        }
+    }
+
+    fn target_size(&self) -> PhysicalSize<u32> {
+        match &self.target {
+            RenderTarget::Window { window, .. } => window.inner_size(),
+            RenderTarget::Offscreen { texture } => {
+                PhysicalSize::new(texture.width(), texture.height())
+            }
+        }
+    }
+
+    /// Gets the size of the frame being rendered to, which can lag behind the window's size until
+    /// the surface is reconfigured.
+    fn frame_size(&self) -> PhysicalSize<u32> {
+        match &self.target {
+            RenderTarget::Window { config, .. } => PhysicalSize::new(config.width, config.height),
+            RenderTarget::Offscreen { texture } => {
+                PhysicalSize::new(texture.width(), texture.height())
+            }
+        }
+    }
 
-            // FIXME: this only works on the main thread
-            self.surface.configure(&self.device, &self.config);
+    fn target_format(&self) -> wgpu::TextureFormat {
+        match &self.target {
+            RenderTarget::Window { config, .. } => config.format,
+            RenderTarget::Offscreen { texture } => texture.format(),
         }
     }
 
-    pub fn register_render_pipeline<T>(&mut self)
+    /// Writes the frame's instances, recreating the instance buffer if they don't fit.
+    /// Only the runs of instances that changed since last frame are written, so a static screen
+    /// writes nothing and a moving sprite only writes its own instance.
+    fn write_instances(&mut self, instances: &[Instance]) {
+        if instances.is_empty() {
+            return;
+        }
+
+        if instances.len() > self.instance_capacity {
+            self.instance_capacity = instances.len().next_power_of_two();
+            self.instance_buffer = create_instance_buffer(&self.device, self.instance_capacity);
+            self.uploaded_instances.clear();
+        }
+
+        let mut start = 0;
+
+        while start < instances.len() {
+            if !instance_changed(instances, &self.uploaded_instances, start) {
+                start += 1;
+                continue;
+            }
+
+            let mut end = start + 1;
+
+            while end < instances.len()
+                && instance_changed(instances, &self.uploaded_instances, end)
+            {
+                end += 1;
+            }
+
+            let offset = (start * std::mem::size_of::<Instance>()) as wgpu::BufferAddress;
+
+            self.queue.write_buffer(
+                &self.instance_buffer,
+                offset,
+                bytemuck::cast_slice(&instances[start..end]),
+            );
+            self.stats.buffer_uploads += 1;
+
+            start = end;
+        }
+
+        self.uploaded_instances.clear();
+        self.uploaded_instances.extend_from_slice(instances);
+    }
+
+    pub fn register_render_pipeline<T>(&mut self, kind: PipelineKind)
     where
         T: Renderable,
     {
-        self.render_pipelines.insert(
-            std::any::type_name::<T>().to_string(),
-            T::create_render_pipeline(
-                &self.device,
-                &self.transform_bind_group_layout,
-                &self.texture_bind_group_layout,
-                &self.config,
-            ),
-        );
+        let registered = RegisteredPipeline {
+            builder: T::create_render_pipeline,
+            #[cfg(debug_assertions)]
+            shader: T::SHADER,
+            module: T::SHADER.create_module(&self.device),
+        };
+
+        self.pipeline_kinds.insert(kind, registered);
     }
 
-    pub fn register_texture(&mut self, path: String, width: u32, height: u32, data: Vec<u8>) {
-        let texture_size = wgpu::Extent3d {
-            width,
-            height,
-            depth_or_array_layers: 1,
+    /// Recompiles shaders that changed on disk and rebuilds the pipelines built from them.
+    #[cfg(debug_assertions)]
+    fn reload_shaders(&mut self) {
+        let Some(watcher) = &mut self.shader_watcher else {
+            return;
         };
 
-        let wgpu_texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            size: texture_size,
+        for (file_name, source) in watcher.changed_shaders() {
+            if file_name == Compositor::SHADER.file_name {
+                self.reload_compositor(&source);
+                continue;
+            }
+
+            let kinds: Vec<PipelineKind> = self
+                .pipeline_kinds
+                .iter()
+                .filter(|(_, registered)| registered.shader.file_name == file_name)
+                .map(|(kind, _)| *kind)
+                .collect();
+
+            if kinds.is_empty() {
+                log::warn!("{} changed, but nothing reloadable uses it", file_name);
+            }
+
+            for kind in kinds {
+                self.reload_pipeline_kind(kind, &file_name, &source);
+            }
+        }
+    }
+
+    /// Rebuilds every pipeline of `kind` from a new shader source. If the shader or any of the
+    /// pipelines fail to compile, the previous pipelines are kept so the game keeps running.
+    #[cfg(debug_assertions)]
+    fn reload_pipeline_kind(&mut self, kind: PipelineKind, file_name: &str, source: &str) {
+        let keys: Vec<PipelineKey> = self
+            .render_pipelines
+            .keys()
+            .filter(|key| key.kind == kind)
+            .copied()
+            .collect();
+
+        let builder = self.pipeline_kinds[&kind].builder;
+        let format = self.target_format();
+
+        let reloaded = self.compile_reloaded(file_name, source, |module| {
+            keys.into_iter()
+                .map(|key| {
+                    let render_pipeline = builder(
+                        &self.device,
+                        module,
+                        &self.camera_bind_group_layout,
+                        &self.texture_bind_group_layout,
+                        format,
+                        key.blend_mode.blend_state(),
+                    );
+
+                    (key, render_pipeline)
+                })
+                .collect::<Vec<_>>()
+        });
+
+        if let Some((module, pipelines)) = reloaded {
+            self.render_pipelines.extend(pipelines);
+            self.pipeline_kinds.get_mut(&kind).unwrap().module = module;
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    fn reload_compositor(&mut self, source: &str) {
+        let reloaded = self.compile_reloaded(Compositor::SHADER.file_name, source, |module| {
+            self.compositor.create_render_pipeline(&self.device, module)
+        });
+
+        if let Some((_, render_pipeline)) = reloaded {
+            self.compositor.set_render_pipeline(render_pipeline);
+        }
+    }
+
+    /// Compiles a reloaded shader and builds whatever's drawn with it. If either fails, the error
+    /// is logged and `None` is returned, so the previous version can be kept.
+    #[cfg(debug_assertions)]
+    fn compile_reloaded<T>(
+        &self,
+        file_name: &str,
+        source: &str,
+        build: impl FnOnce(&wgpu::ShaderModule) -> T,
+    ) -> Option<(wgpu::ShaderModule, T)> {
+        // Validation errors would otherwise be fatal, so catch them instead.
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let module = create_shader_module(&self.device, file_name, source);
+        let built = build(&module);
+
+        if let Some(e) = futures::executor::block_on(self.device.pop_error_scope()) {
+            log::error!(
+                "Error reloading {}, keeping the previous version: {}",
+                file_name,
+                e
+            );
+            return None;
+        }
+
+        log::info!("Reloaded {}", file_name);
+        Some((module, built))
+    }
+
+    /// Builds the render pipeline for `key` if it hasn't been built yet.
+    fn create_render_pipeline(&mut self, key: PipelineKey) {
+        if self.render_pipelines.contains_key(&key) {
+            return;
+        }
+
+        let registered = self
+            .pipeline_kinds
+            .get(&key.kind)
+            .expect("pipeline kind should be registered");
+
+        let render_pipeline = (registered.builder)(
+            &self.device,
+            &registered.module,
+            &self.camera_bind_group_layout,
+            &self.texture_bind_group_layout,
+            self.target_format(),
+            key.blend_mode.blend_state(),
+        );
+
+        self.render_pipelines.insert(key, render_pipeline);
+    }
+
+    fn create_atlas_page(&mut self, page: usize, size: u32, format: PixelFormat) {
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            // NxBitmap data is in the "reversed" bgra format.
-            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            format: format.to_wgpu(),
             usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            label: Some(&path),
+            label: Some(&format!("atlas page {}", page)),
             view_formats: &[],
         });
 
-        self.queue.write_texture(
-            wgpu::ImageCopyTexture {
-                texture: &wgpu_texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &data,
-            wgpu::ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * texture_size.width),
-                rows_per_image: Some(texture_size.height),
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        self.atlas_pages.insert(
+            page,
+            AtlasPageTexture {
+                texture,
+                view,
+                bind_groups: HashMap::new(),
             },
-            texture_size,
         );
+    }
 
-        let view = wgpu_texture.create_view(&wgpu::TextureViewDescriptor::default());
+    /// Creates the bind group for drawing an atlas page with `policy` if it doesn't exist yet.
+    fn create_page_bind_group(&mut self, page: usize, policy: SamplerPolicy) {
+        let Some(page_texture) = self.atlas_pages.get_mut(&page) else {
+            log::error!("Atlas page {} doesn't exist", page);
+            return;
+        };
 
-        let sampler = self.device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            // TODO: play with these to see what looks best
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
+        if page_texture.bind_groups.contains_key(&policy) {
+            return;
+        }
+
+        let sampler = self.samplers.get(&self.device, policy);
 
-        let texture_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+        let page_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
             layout: &self.texture_bind_group_layout,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&view),
+                    resource: wgpu::BindingResource::TextureView(&page_texture.view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
+                    resource: wgpu::BindingResource::Sampler(sampler),
                 },
             ],
-            label: None,
+            label: Some(&format!("atlas page {} bind group", page)),
         });
 
-        self.texture_bind_groups
-            .insert(path, (texture_bind_group, wgpu_texture));
+        page_texture.bind_groups.insert(policy, page_bind_group);
+    }
+
+    fn upload_atlas_region(&mut self, region: AtlasRegion, data: Vec<u8>) {
+        let page_texture = match self.atlas_pages.get_mut(&region.page) {
+            Some(page_texture) => page_texture,
+            None => {
+                log::error!("Atlas page {} doesn't exist", region.page);
+                return;
+            }
+        };
+
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &page_texture.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: region.x,
+                    y: region.y,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            &data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * region.width),
+                rows_per_image: Some(region.height),
+            },
+            wgpu::Extent3d {
+                width: region.width,
+                height: region.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+/// Where the renderer draws its frames.
+enum RenderTarget {
+    Window {
+        window: Arc<Window>,
+        surface: wgpu::Surface<'static>,
+        config: wgpu::SurfaceConfiguration,
+    },
+    Offscreen {
+        texture: wgpu::Texture,
+    },
+}
+
+/// Whether the instance at `index` is different from the one uploaded there last frame.
+/// Instances hold floats, so they're compared bytewise.
+fn instance_changed(instances: &[Instance], uploaded: &[Instance], index: usize) -> bool {
+    match uploaded.get(index) {
+        Some(uploaded) => bytemuck::bytes_of(uploaded) != bytemuck::bytes_of(&instances[index]),
+        None => true,
+    }
+}
+
+fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("instance buffer"),
+        size: (capacity * std::mem::size_of::<Instance>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// A kind of render pipeline that can be built, see `Renderer::register_render_pipeline`.
+struct RegisteredPipeline {
+    builder: PipelineBuilder,
+
+    // The shader's file name is needed to find the pipelines to rebuild when it's reloaded.
+    #[cfg(debug_assertions)]
+    shader: Shader,
+    module: wgpu::ShaderModule,
+}
+
+/// An atlas page's texture, along with a bind group for each sampler policy it's drawn with.
+struct AtlasPageTexture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    bind_groups: HashMap<SamplerPolicy, wgpu::BindGroup>,
+}
+
+/// A run of consecutive render items that can be drawn with one instanced draw call.
+struct Batch {
+    pipeline: PipelineKey,
+    atlas_page: Option<usize>,
+    sampler: SamplerPolicy,
+    clip: Option<ClipRect>,
+    instances: Range<u32>,
+}
+
+fn get_batches(items: &[RenderItem]) -> Vec<Batch> {
+    let mut batches: Vec<Batch> = Vec::new();
+
+    for (i, item) in items.iter().enumerate() {
+        match batches.last_mut() {
+            Some(batch)
+                if batch.pipeline == item.pipeline
+                    && batch.atlas_page == item.atlas_page
+                    && batch.sampler == item.sampler
+                    && batch.clip == item.clip =>
+            {
+                batch.instances.end += 1;
+            }
+            _ => batches.push(Batch {
+                pipeline: item.pipeline,
+                atlas_page: item.atlas_page,
+                sampler: item.sampler,
+                clip: item.clip,
+                instances: i as u32..i as u32 + 1,
+            }),
+        }
+    }
+
+    batches
+}
+
+async fn request_device(adapter: &wgpu::Adapter) -> (wgpu::Device, wgpu::Queue) {
+    // Timestamp queries are only used for stats, so they're optional.
+    let optional_features = adapter.features() & wgpu::Features::TIMESTAMP_QUERY;
+
+    adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                required_features: optional_features,
+                required_limits: wgpu::Limits::default(),
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None,
+        )
+        .await
+        .expect("device and queue should be created")
+}
+
+fn create_offscreen_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        // Rgba so frames can be read back into an `image::RgbaImage` without swizzling.
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        label: Some("offscreen target"),
+        view_formats: &[],
+    })
+}
+
+/// Copies an Rgba8 texture into a buffer and waits for it to be mapped.
+fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> image::RgbaImage {
+    let width = texture.width();
+    let height = texture.height();
+
+    // Each row in the buffer has to be padded to a multiple of 256 bytes.
+    let unpadded_bytes_per_row = 4 * width;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("readback encoder"),
+    });
+
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        texture.size(),
+    );
+
+    queue.submit(iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    slice.map_async(wgpu::MapMode::Read, |result| {
+        if let Err(e) = result {
+            log::error!("Error mapping readback buffer: {}", e);
+        }
+    });
+    device.poll(wgpu::Maintain::Wait);
+
+    let mut data = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+
+    for row in slice
+        .get_mapped_range()
+        .chunks_exact(padded_bytes_per_row as usize)
+    {
+        data.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+    }
+
+    buffer.unmap();
+
+    // Window surfaces are usually bgra, so swap the red and blue channels back.
+    if matches!(
+        texture.format(),
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+    ) {
+        for pixel in data.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
     }
+
+    image::RgbaImage::from_raw(width, height, data).expect("readback data should fit the image")
+}
+
+/// An error the renderer can't recover from.
+#[derive(Debug)]
+pub enum RendererError {
+    /// There isn't enough memory left to render another frame.
+    OutOfMemory,
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::OutOfMemory => write!(f, "out of memory"),
+        }
+    }
+}
+
+/// Everything needed to draw a frame, in the order it has to be processed.
+pub struct Frame {
+    pub id: u64,
+    pub commands: Vec<RenderCommand>,
+}
+
+pub enum RenderCommand {
+    Update(RenderUpdate),
+    Draw(RenderItem),
+}
+
+/// Sent back to `RendererManager` once a frame's updates have been submitted to the GPU.
+#[derive(Copy, Clone, Debug)]
+pub struct FrameAck {
+    pub frame: u64,
+
+    /// Whether the frame was drawn, or skipped because a newer frame was already queued.
+    pub drawn: bool,
 }
 
 pub enum RendererEvent {
-    Render(Vec<RenderUpdate>, Vec<RenderItem>),
+    Render(Frame),
     Resize(PhysicalSize<u32>),
+
+    /// Saves the next frame as a PNG in the screenshots directory.
+    Screenshot,
 }
 
 pub enum RenderUpdate {
-    CreateTextureBindGroup {
-        path: String,
-        width: u32,
-        height: u32,
-        data: Vec<u8>,
+    CreateAtlasPage {
+        page: usize,
+        size: u32,
+        format: PixelFormat,
     },
-    CreateIndexBuffer {
-        id: Uuid,
+    UploadAtlasRegion {
+        region: AtlasRegion,
         data: Vec<u8>,
     },
-    CreateVertexBuffer {
-        id: Uuid,
-        data: Vec<u8>,
+    ReleaseAtlasRegion {
+        region: AtlasRegion,
     },
-    UpdateTransformUniform {
-        id: Uuid,
-        uniform: Uniform,
+    DestroyAtlasPage {
+        page: usize,
     },
+    UpdateCamera(CameraUniform),
+    UpdatePostProcess(PostProcessUniform),
 }
 
 pub struct RenderItem {
-    pub(crate) id: Uuid,
-    pub(crate) type_name: String,
-    pub(crate) texture_name: Option<String>,
-    pub(crate) layer: usize,
-    pub(crate) range: Range<u32>,
+    pub(crate) pipeline: PipelineKey,
+    pub(crate) atlas_page: Option<usize>,
+    pub(crate) order: RenderOrder,
+    pub(crate) instance: Instance,
+
+    /// The area the item is clipped to, or `None` to draw it unclipped.
+    pub(crate) clip: Option<ClipRect>,
+
+    /// How the item's texture is filtered.
+    pub(crate) sampler: SamplerPolicy,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        component::{Camera, Colour},
+        graphics::{extract_shapes, RenderLayer, RendererManager},
+        state::State,
+    };
+
+    use super::*;
+
+    /// How far each channel of a pixel can be from the golden image, to allow for differences
+    /// between adapters.
+    const TOLERANCE: u8 = 2;
+
+    #[test]
+    #[ignore = "needs a GPU or software adapter"]
+    fn shapes_match_the_golden_image() {
+        let (sender, receiver) = mpsc::sync_channel(8);
+        let (ack_sender, ack_receiver) = mpsc::channel();
+        let mut renderer =
+            futures::executor::block_on(Renderer::new_headless(64, 48, receiver, ack_sender));
+
+        let mut state = State::new();
+        state.insert_resource(Camera::new(64.0, 48.0));
+
+        // Spawned out of order, so this also checks they're drawn by layer then insertion.
+        let shapes = [
+            Shape::rect(0.0, 40.0, 64.0, 8.0, Colour::rgb(0, 255, 0)).with_layer(RenderLayer::Ui),
+            Shape::rect(8.0, 8.0, 24.0, 16.0, Colour::rgb(255, 0, 0)),
+            Shape::rect(24.0, 16.0, 24.0, 24.0, Colour::rgb(0, 0, 255)),
+            Shape::rect(4.0, 4.0, 16.0, 16.0, Colour::rgb(0, 0, 0))
+                .with_layer(RenderLayer::Background),
+        ];
+
+        for shape in shapes {
+            state.entities.spawn().with(shape);
+        }
+
+        let mut renderer_manager = RendererManager::new(sender, ack_receiver, 1);
+        renderer_manager.register_extractor(extract_shapes);
+        renderer_manager.generate_and_send_events(&mut state);
+
+        renderer.process_pending_events().unwrap();
+        let frame = renderer.read_frame().unwrap();
+
+        let golden = image::open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/golden/shapes.png"
+        ))
+        .unwrap()
+        .into_rgba8();
+
+        assert_eq!(frame.dimensions(), golden.dimensions());
+
+        let different = frame
+            .pixels()
+            .zip(golden.pixels())
+            .filter(|(actual, expected)| {
+                actual
+                    .0
+                    .iter()
+                    .zip(expected.0)
+                    .any(|(actual, expected)| actual.abs_diff(expected) > TOLERANCE)
+            })
+            .count();
+
+        assert_eq!(
+            different, 0,
+            "{} pixels differ from the golden image",
+            different
+        );
+    }
 }
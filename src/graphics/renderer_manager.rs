@@ -1,138 +1,713 @@
-use std::{collections::HashSet, sync::mpsc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::mpsc,
+};
 
 use uuid::Uuid;
 
-use crate::{component::Camera, state::State};
+use crate::{
+    component::{Camera, Colour, Transform},
+    resource::ScreenEffects,
+    state::State,
+};
 
-use super::{renderer::RenderUpdate, RenderItem, RenderableV2, RendererEvent, Texture, Uniform};
+use super::{
+    atlas::{Atlas, AtlasRegion},
+    composite::PostProcessUniform,
+    renderer::{Frame, RenderCommand, RenderUpdate},
+    BlendMode, CameraUniform, FrameAck, Instance, PipelineKey, PipelineKind, RenderItem,
+    RenderOrder, RenderableV2, RendererEvent, SamplerPolicy, Shape,
+};
+
+/// The number of frames a texture has to go unused before it's released.
+/// This stops textures that are swapped back and forth, ex. button states, from being re-uploaded
+/// every time they're used.
+const RELEASE_AFTER_FRAMES: u64 = 120;
+
+/// Gets everything of one kind that's drawn from `State`, ex. sprites or shapes.
+pub type Extractor = for<'a> fn(&'a State, &mut Extraction<'a>);
+
+/// Everything being drawn this frame, gathered by the registered `Extractor`s.
+#[derive(Default)]
+pub struct Extraction<'a> {
+    extracted: Vec<Extracted<'a>>,
+}
+
+impl<'a> Extraction<'a> {
+    /// Adds something textured to be drawn, uploading its texture if it hasn't been already.
+    pub fn push_renderable(&mut self, renderable: &'a dyn RenderableV2) {
+        self.extracted.push(Extracted::Renderable(renderable));
+    }
+
+    pub fn push_shape(&mut self, shape: &'a Shape) {
+        self.extracted.push(Extracted::Shape(shape));
+    }
+
+    fn renderables(&self) -> impl Iterator<Item = &'a dyn RenderableV2> + '_ {
+        self.extracted
+            .iter()
+            .filter_map(|extracted| match extracted {
+                Extracted::Renderable(renderable) => Some(*renderable),
+                Extracted::Shape(_) => None,
+            })
+    }
+}
+
+enum Extracted<'a> {
+    Renderable(&'a dyn RenderableV2),
+    Shape(&'a Shape),
+}
 
 // TODO: kinda hate this name...
 pub struct RendererManager {
-    sender: mpsc::Sender<RendererEvent>,
-    initialized_entities: HashSet<Uuid>,
-    initialized_textures: HashSet<String>,
-    initialized_fonts: HashSet<String>,
+    sender: mpsc::SyncSender<RendererEvent>,
+    ack_receiver: mpsc::Receiver<FrameAck>,
+    atlas: Atlas,
+    frame: u64,
+
+    /// The number of frames that can be sent to the renderer before it acknowledges them.
+    /// Past this the renderer has fallen behind, so frames are coalesced instead of queued up.
+    max_frames_in_flight: u64,
+
+    /// The last frame sent to the renderer.
+    last_sent_frame: u64,
+
+    /// The last frame the renderer acknowledged. Every update sent up to and including this frame
+    /// has been submitted to the GPU.
+    last_acknowledged_frame: u64,
+
+    /// Updates from frames that weren't sent, to be sent along with the next frame.
+    pending_updates: Vec<RenderCommand>,
+
+    /// The camera and effects last sent to the renderer, so they're only sent again when they
+    /// change.
+    sent_camera: Option<CameraUniform>,
+    sent_post_process: Option<PostProcessUniform>,
+
+    /// Atlas pages that currently exist in the renderer.
+    initialized_pages: HashSet<usize>,
+
+    /// Entities that were rendered last frame, mapped to the path of their texture.
+    initialized_entities: HashMap<Uuid, String>,
+
+    /// Textures that have been uploaded, keyed by path.
+    initialized_textures: HashMap<String, TextureEntry>,
+
+    /// The instance last computed for each entity, so it's only recomputed when the entity changes.
+    instances: HashMap<Uuid, CachedInstance>,
+
+    /// Gets each kind of thing that's drawn from `State`, see `register_extractor`.
+    extractors: Vec<Extractor>,
 }
 
 impl RendererManager {
-    pub fn new(sender: mpsc::Sender<RendererEvent>) -> Self {
+    pub fn new(
+        sender: mpsc::SyncSender<RendererEvent>,
+        ack_receiver: mpsc::Receiver<FrameAck>,
+        max_frames_in_flight: u32,
+    ) -> Self {
         Self {
             sender,
-            initialized_entities: HashSet::new(),
-            initialized_textures: HashSet::new(),
-            initialized_fonts: HashSet::new(),
+            ack_receiver,
+            atlas: Atlas::new(),
+            frame: 0,
+            max_frames_in_flight: max_frames_in_flight.into(),
+            last_sent_frame: 0,
+            last_acknowledged_frame: 0,
+            pending_updates: Vec::new(),
+            sent_camera: None,
+            sent_post_process: None,
+            initialized_pages: HashSet::new(),
+            initialized_entities: HashMap::new(),
+            initialized_textures: HashMap::new(),
+            instances: HashMap::new(),
+            extractors: Vec::new(),
+        }
+    }
+
+    /// Registers a kind of thing that's drawn, ex. mobs or drops, so it's drawn without having to
+    /// be handled here. Kinds are gathered in the order they're registered.
+    pub fn register_extractor(&mut self, extractor: Extractor) {
+        self.extractors.push(extractor);
+    }
+
+    /// Gets everything registered to be drawn in `state`.
+    fn extract<'a>(&self, state: &'a State) -> Extraction<'a> {
+        let mut extraction = Extraction::default();
+
+        for extractor in self.extractors.iter() {
+            extractor(state, &mut extraction);
         }
+
+        extraction
     }
 
     pub fn generate_and_send_events(&mut self, state: &mut State) {
-        let updates = self.get_render_updates(state);
-        let items = self.get_render_items(state);
+        self.frame += 1;
+        self.receive_acks();
+
+        let extraction = self.extract(state);
+        let updates = self.get_render_updates(state, &extraction);
 
-        // TODO we can probably just send a single vec, push updates first, then items.
-        if let Err(e) = self.sender.send(RendererEvent::Render(updates, items)) {
-            log::error!("Error sending Render event: {}", e);
+        // Only the newest camera and effects matter, so older unsent ones are dropped once there's
+        // a newer one to send instead.
+        let camera_changed = updates
+            .iter()
+            .any(|update| matches!(update, RenderUpdate::UpdateCamera(_)));
+        let post_process_changed = updates
+            .iter()
+            .any(|update| matches!(update, RenderUpdate::UpdatePostProcess(_)));
+
+        self.pending_updates.retain(|command| match command {
+            RenderCommand::Update(RenderUpdate::UpdateCamera(_)) => !camera_changed,
+            RenderCommand::Update(RenderUpdate::UpdatePostProcess(_)) => !post_process_changed,
+            _ => true,
+        });
+
+        self.pending_updates
+            .extend(updates.into_iter().map(RenderCommand::Update));
+
+        // The renderer has fallen behind, so skip this frame instead of queueing it up.
+        // Its updates are kept and sent along with the next frame, so nothing is lost.
+        if self.last_sent_frame - self.last_acknowledged_frame >= self.max_frames_in_flight {
+            return;
+        }
+
+        let mut commands = std::mem::take(&mut self.pending_updates);
+        commands.extend(
+            self.get_render_items(&extraction)
+                .into_iter()
+                .map(RenderCommand::Draw),
+        );
+
+        let frame = Frame {
+            id: self.frame,
+            commands,
+        };
+
+        match self.sender.try_send(RendererEvent::Render(frame)) {
+            Ok(_) => self.last_sent_frame = self.frame,
+            Err(mpsc::TrySendError::Full(RendererEvent::Render(frame))) => {
+                // The frame's draws will be stale by the next frame, but its updates aren't.
+                self.pending_updates = frame
+                    .commands
+                    .into_iter()
+                    .filter(|command| matches!(command, RenderCommand::Update(_)))
+                    .collect();
+            }
+            Err(e) => log::error!("Error sending Render event: {}", e),
         }
     }
 
-    fn get_render_updates(&mut self, state: &mut State) -> Vec<RenderUpdate> {
+    fn receive_acks(&mut self) {
+        for ack in self.ack_receiver.try_iter() {
+            if !ack.drawn {
+                log::debug!("Frame {} was skipped by the renderer", ack.frame);
+            }
+
+            self.last_acknowledged_frame = self.last_acknowledged_frame.max(ack.frame);
+        }
+    }
+
+    fn get_render_updates(&mut self, state: &State, extraction: &Extraction) -> Vec<RenderUpdate> {
         let mut updates = Vec::new();
 
-        let camera = state.get_resource::<Camera>().unwrap();
+        let camera = CameraUniform::compute(&state.get_resource::<Camera>().unwrap());
 
-        for sprite in state.sprites.iter() {
-            updates.append(&mut self.get_updates_for_component(sprite, &camera));
+        if self.sent_camera != Some(camera) {
+            updates.push(RenderUpdate::UpdateCamera(camera));
+            self.sent_camera = Some(camera);
         }
 
-        for button in state.buttons.iter() {
-            updates.append(&mut self.get_updates_for_component(button, &camera));
+        if let Some(effects) = state.get_resource::<ScreenEffects>() {
+            let post_process = PostProcessUniform::compute(&effects);
+
+            if self.sent_post_process != Some(post_process) {
+                updates.push(RenderUpdate::UpdatePostProcess(post_process));
+                self.sent_post_process = Some(post_process);
+            }
         }
 
-        for text in state.text.iter() {
-            updates.append(&mut self.get_updates_for_component(text, &camera));
+        let mut entities = HashSet::new();
+
+        for renderable in extraction.renderables() {
+            updates.append(&mut self.get_updates_for_component(renderable));
+            entities.insert(*renderable.id());
         }
 
+        self.release_removed_entities(&entities);
+        updates.append(&mut self.release_unused_textures());
+
         updates
     }
 
-    fn get_render_items(&mut self, state: &mut State) -> Vec<RenderItem> {
+    fn get_render_items(&mut self, extraction: &Extraction) -> Vec<RenderItem> {
         let mut items = Vec::new();
 
-        for sprite in state.sprites.iter() {
-            items.push(RenderItem {
-                id: *sprite.id(),
-                type_name: std::any::type_name::<Texture>().to_string(),
-                texture_name: Some(sprite.texture().path.clone()),
-                range: sprite.texture().index_buffer_range.clone(),
-                layer: sprite.transform().z as usize,
-            });
+        for extracted in extraction.extracted.iter() {
+            match extracted {
+                Extracted::Renderable(renderable) => {
+                    items.push(RenderItem {
+                        pipeline: PipelineKey {
+                            kind: PipelineKind::Texture,
+                            blend_mode: renderable.blend_mode(),
+                        },
+                        atlas_page: Some(self.atlas_region(*renderable).page),
+                        order: get_render_order(*renderable, items.len()),
+                        instance: self.get_instance(*renderable),
+                        clip: renderable.clip(),
+                        sampler: renderable.sampler_policy(),
+                    });
+                }
+                Extracted::Shape(shape) => {
+                    for instance in shape.instances() {
+                        items.push(RenderItem {
+                            pipeline: PipelineKey {
+                                kind: PipelineKind::Primitive,
+                                blend_mode: BlendMode::Alpha,
+                            },
+                            atlas_page: None,
+                            order: RenderOrder {
+                                layer: shape.layer(),
+                                z: 0,
+                                insertion: items.len(),
+                            },
+                            instance,
+                            clip: shape.clip,
+                            // Shapes aren't textured, so this is only used for batching.
+                            sampler: SamplerPolicy::Nearest,
+                        });
+                    }
+                }
+            }
         }
 
-        for button in state.buttons.iter() {
-            items.push(RenderItem {
-                id: *button.id(),
-                type_name: std::any::type_name::<Texture>().to_string(),
-                texture_name: Some(button.texture().path.clone()),
-                range: button.texture().index_buffer_range.clone(),
-                layer: button.transform().z as usize,
-            });
-        }
+        // Sort render items back to front, since they're drawn in order.
+        items.sort_by_key(|item| item.order);
+        items
+    }
 
-        for text in state.text.iter() {
-            items.push(RenderItem {
-                id: *text.id(),
-                type_name: std::any::type_name::<Texture>().to_string(),
-                texture_name: Some(text.texture().path.clone()),
-                range: text.texture().index_buffer_range.clone(),
-                layer: text.transform().z as usize,
-            });
+    fn atlas_region(&self, component: &dyn RenderableV2) -> AtlasRegion {
+        self.initialized_textures
+            .get(&component.texture().path)
+            .expect("texture should be uploaded before being rendered")
+            .region
+    }
+
+    fn get_instance(&mut self, component: &dyn RenderableV2) -> Instance {
+        let texture = component.texture();
+        let uv_rect = self.atlas_region(component).map_uv_rect(texture.uv_rect);
+        let transform = *component.transform();
+        let colour = component.colour();
+        let frame_progress = component.frame_progress();
+
+        if let Some(cached) = self.instances.get(component.id()) {
+            if cached.texture == texture.path
+                && cached.uv_rect == uv_rect
+                && cached.transform == transform
+                && cached.colour == colour
+                && cached.frame_progress == frame_progress
+            {
+                return cached.instance;
+            }
         }
 
-        // Sort render items by their z position/layer.
-        // High layer = front, low layer = back.
-        // TODO: instead of this we should have a RenderLayer enum, ex. UI, Foreground, Background, ...
-        items.sort_by(|a, b| b.layer.cmp(&a.layer));
-        items
+        let instance = Instance::compute(texture, &transform, colour, uv_rect, frame_progress);
+
+        self.instances.insert(
+            *component.id(),
+            CachedInstance {
+                texture: texture.path.clone(),
+                uv_rect,
+                transform,
+                colour,
+                frame_progress,
+                instance,
+            },
+        );
+
+        instance
     }
 
-    fn get_updates_for_component<T: RenderableV2>(
-        &mut self,
-        component: &T,
-        camera: &Camera,
-    ) -> Vec<RenderUpdate> {
+    fn get_updates_for_component(&mut self, component: &dyn RenderableV2) -> Vec<RenderUpdate> {
         let mut updates = Vec::new();
-
-        let id = component.id();
         let texture = component.texture();
-        let transform = component.transform();
 
-        if !self.initialized_entities.contains(id) {
-            updates.push(RenderUpdate::CreateIndexBuffer {
-                id: *id,
-                data: texture.index_buffer.clone(),
-            });
+        if !self.initialized_textures.contains_key(&texture.path) {
+            let region = self
+                .atlas
+                .allocate(texture.width, texture.height, texture.format);
+
+            if self.initialized_pages.insert(region.page) {
+                updates.push(RenderUpdate::CreateAtlasPage {
+                    page: region.page,
+                    size: self.atlas.page_size(region.page),
+                    format: self.atlas.page_format(region.page),
+                });
+            }
 
-            updates.push(RenderUpdate::CreateVertexBuffer {
-                id: *id,
-                data: texture.vertex_buffer.clone(),
+            updates.push(RenderUpdate::UploadAtlasRegion {
+                region,
+                data: texture.data.clone(),
             });
 
-            self.initialized_entities.insert(*id);
+            self.initialized_textures.insert(
+                texture.path.clone(),
+                TextureEntry {
+                    region,
+                    references: 0,
+                    unused_since: None,
+                },
+            );
         }
 
-        if !self.initialized_textures.contains(&texture.path) {
-            self.initialized_textures.insert(texture.path.clone());
+        // Move the entity's reference over if its texture changed, ex. a button being hovered.
+        let previous = self
+            .initialized_entities
+            .insert(*component.id(), texture.path.clone());
 
-            updates.push(RenderUpdate::CreateTextureBindGroup {
-                path: texture.path.clone(),
-                width: texture.width,
-                height: texture.height,
-                data: texture.data.clone(),
-            });
+        if previous.as_ref() != Some(&texture.path) {
+            self.add_texture_reference(&texture.path);
+
+            if let Some(previous) = previous {
+                self.remove_texture_reference(&previous);
+            }
+        }
+
+        updates
+    }
+
+    /// Drops the texture references of entities that are no longer in `State`.
+    fn release_removed_entities(&mut self, entities: &HashSet<Uuid>) {
+        let removed: Vec<Uuid> = self
+            .initialized_entities
+            .keys()
+            .filter(|id| !entities.contains(id))
+            .copied()
+            .collect();
+
+        for id in removed {
+            self.instances.remove(&id);
+
+            if let Some(path) = self.initialized_entities.remove(&id) {
+                self.remove_texture_reference(&path);
+            }
         }
+    }
+
+    /// Frees the atlas space of textures that haven't been used for a while, destroying any pages
+    /// that end up empty.
+    fn release_unused_textures(&mut self) -> Vec<RenderUpdate> {
+        let mut updates = Vec::new();
+
+        let unused: Vec<String> = self
+            .initialized_textures
+            .iter()
+            .filter(|(_, entry)| {
+                entry
+                    .unused_since
+                    .is_some_and(|frame| self.frame - frame >= RELEASE_AFTER_FRAMES)
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
 
-        let uniform = Uniform::compute(texture, transform, camera);
-        updates.push(RenderUpdate::UpdateTransformUniform { id: *id, uniform });
+        for path in unused {
+            let region = self.initialized_textures.remove(&path).unwrap().region;
+
+            if self.atlas.deallocate(&region) {
+                self.initialized_pages.remove(&region.page);
+                updates.push(RenderUpdate::DestroyAtlasPage { page: region.page });
+            } else {
+                updates.push(RenderUpdate::ReleaseAtlasRegion { region });
+            }
+        }
 
         updates
     }
+
+    fn add_texture_reference(&mut self, path: &str) {
+        if let Some(entry) = self.initialized_textures.get_mut(path) {
+            entry.references += 1;
+            entry.unused_since = None;
+        }
+    }
+
+    fn remove_texture_reference(&mut self, path: &str) {
+        if let Some(entry) = self.initialized_textures.get_mut(path) {
+            entry.references -= 1;
+
+            if entry.references == 0 {
+                entry.unused_since = Some(self.frame);
+            }
+        }
+    }
+}
+
+/// Extracts every entity with a `T` component, for registering common kinds of renderables.
+pub fn extract_renderables<'a, T: RenderableV2 + 'static>(
+    state: &'a State,
+    extraction: &mut Extraction<'a>,
+) {
+    for (_, renderable) in state.entities.query::<T>() {
+        extraction.push_renderable(renderable);
+    }
+}
+
+/// Extracts every entity with a `Shape` component.
+pub fn extract_shapes<'a>(state: &'a State, extraction: &mut Extraction<'a>) {
+    for (_, shape) in state.entities.query::<Shape>() {
+        extraction.push_shape(shape);
+    }
+}
+
+fn get_render_order(component: &dyn RenderableV2, insertion: usize) -> RenderOrder {
+    RenderOrder {
+        layer: component.layer(),
+        z: component.texture().z.unwrap_or(0),
+        insertion,
+    }
+}
+
+/// An entity's instance, along with everything it was computed from.
+struct CachedInstance {
+    texture: String,
+    uv_rect: [f32; 4],
+    transform: Transform,
+    colour: Colour,
+    frame_progress: f32,
+    instance: Instance,
+}
+
+/// A texture that has been uploaded to the atlas.
+struct TextureEntry {
+    region: AtlasRegion,
+
+    /// The number of entities currently using the texture.
+    references: usize,
+
+    /// The frame the texture stopped being used, if it isn't being used.
+    unused_since: Option<u64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graphics::{tests::TestRenderable, RenderLayer};
+
+    use super::*;
+
+    struct Harness {
+        manager: RendererManager,
+        receiver: mpsc::Receiver<RendererEvent>,
+        ack_sender: mpsc::Sender<FrameAck>,
+        state: State,
+    }
+
+    impl Harness {
+        fn new(channel_bound: usize, max_frames_in_flight: u32) -> Self {
+            let (sender, receiver) = mpsc::sync_channel(channel_bound);
+            let (ack_sender, ack_receiver) = mpsc::channel();
+
+            let mut manager = RendererManager::new(sender, ack_receiver, max_frames_in_flight);
+            manager.register_extractor(extract_renderables::<TestRenderable>);
+
+            let mut state = State::new();
+            state.insert_resource(Camera::new(800.0, 600.0));
+
+            Self {
+                manager,
+                receiver,
+                ack_sender,
+                state,
+            }
+        }
+
+        fn send(&mut self) {
+            self.manager.generate_and_send_events(&mut self.state);
+        }
+
+        /// Gets the frames the renderer would receive, without acknowledging them.
+        fn received(&self) -> Vec<Frame> {
+            self.receiver
+                .try_iter()
+                .map(|event| match event {
+                    RendererEvent::Render(frame) => frame,
+                    _ => panic!("only frames should be sent"),
+                })
+                .collect()
+        }
+
+        fn ack(&self, frame: u64, drawn: bool) {
+            self.ack_sender.send(FrameAck { frame, drawn }).unwrap();
+        }
+
+        /// Sends a frame and acknowledges it, as a renderer that keeps up would.
+        fn send_and_ack(&mut self) -> Frame {
+            self.send();
+
+            let frame = self.received().pop().expect("a frame should be sent");
+            self.ack(frame.id, true);
+            frame
+        }
+    }
+
+    fn updates(frame: &Frame) -> impl Iterator<Item = &RenderUpdate> {
+        frame.commands.iter().filter_map(|command| match command {
+            RenderCommand::Update(update) => Some(update),
+            RenderCommand::Draw(_) => None,
+        })
+    }
+
+    fn draws(frame: &Frame) -> usize {
+        frame
+            .commands
+            .iter()
+            .filter(|command| matches!(command, RenderCommand::Draw(_)))
+            .count()
+    }
+
+    #[test]
+    fn frames_are_skipped_while_too_many_are_in_flight() {
+        let mut harness = Harness::new(8, 2);
+
+        for _ in 0..4 {
+            harness.send();
+        }
+
+        let frames = harness.received();
+        assert_eq!(
+            frames.iter().map(|frame| frame.id).collect::<Vec<_>>(),
+            [1, 2]
+        );
+    }
+
+    #[test]
+    fn acknowledged_frames_make_room_for_more() {
+        let mut harness = Harness::new(8, 1);
+
+        harness.send();
+        harness.send();
+        assert_eq!(harness.received().len(), 1);
+
+        harness.ack(1, true);
+        harness.send();
+        assert_eq!(harness.received()[0].id, 3);
+    }
+
+    #[test]
+    fn skipped_frames_are_acknowledged_too() {
+        let mut harness = Harness::new(8, 1);
+
+        harness.send();
+        harness.ack(1, false);
+        harness.send();
+
+        assert_eq!(harness.received().len(), 2);
+    }
+
+    #[test]
+    fn updates_of_skipped_frames_are_sent_with_the_next_frame() {
+        let mut harness = Harness::new(8, 1);
+        harness.send_and_ack();
+
+        harness.send();
+        harness
+            .state
+            .entities
+            .spawn()
+            .with(TestRenderable::new(RenderLayer::World, None));
+        harness.send();
+        assert_eq!(harness.received().len(), 1);
+
+        harness.ack(2, true);
+        harness.send();
+
+        let frame = harness.received().pop().unwrap();
+        assert!(
+            updates(&frame).any(|update| matches!(update, RenderUpdate::UploadAtlasRegion { .. }))
+        );
+        assert_eq!(draws(&frame), 1);
+    }
+
+    #[test]
+    fn only_updates_are_kept_when_the_channel_is_full() {
+        let mut harness = Harness::new(1, 8);
+        harness
+            .state
+            .entities
+            .spawn()
+            .with(TestRenderable::new(RenderLayer::World, None));
+
+        harness.send();
+        harness.state.get_resource_mut::<Camera>().unwrap().right = 400.0;
+        harness.send();
+
+        let pending = &harness.manager.pending_updates;
+        assert_eq!(pending.len(), 1);
+        assert!(matches!(
+            pending[0],
+            RenderCommand::Update(RenderUpdate::UpdateCamera(_))
+        ));
+
+        assert_eq!(harness.received().len(), 1);
+        harness.send();
+
+        let frame = harness.received().pop().unwrap();
+        assert_eq!(frame.id, 3);
+        assert!(updates(&frame).any(|update| matches!(update, RenderUpdate::UpdateCamera(_))));
+        assert_eq!(draws(&frame), 1);
+    }
+
+    #[test]
+    fn textures_are_released_once_unused_for_long_enough() {
+        let mut harness = Harness::new(8, 1);
+        let entity = harness
+            .state
+            .entities
+            .spawn()
+            .with(TestRenderable::new(RenderLayer::World, None))
+            .id();
+
+        let frame = harness.send_and_ack();
+        assert!(
+            updates(&frame).any(|update| matches!(update, RenderUpdate::UploadAtlasRegion { .. }))
+        );
+
+        harness.state.entities.despawn(entity);
+
+        for _ in 0..RELEASE_AFTER_FRAMES {
+            let frame = harness.send_and_ack();
+            assert_eq!(updates(&frame).count(), 0);
+        }
+
+        let frame = harness.send_and_ack();
+        assert!(
+            updates(&frame).any(|update| matches!(update, RenderUpdate::DestroyAtlasPage { .. }))
+        );
+    }
+
+    #[test]
+    fn textures_used_again_before_being_released_are_kept() {
+        let mut harness = Harness::new(8, 1);
+        let entity = harness
+            .state
+            .entities
+            .spawn()
+            .with(TestRenderable::new(RenderLayer::World, None))
+            .id();
+
+        harness.send_and_ack();
+        harness.state.entities.despawn(entity);
+        harness.send_and_ack();
+
+        harness
+            .state
+            .entities
+            .spawn()
+            .with(TestRenderable::new(RenderLayer::World, None));
+
+        for _ in 0..RELEASE_AFTER_FRAMES * 2 {
+            let frame = harness.send_and_ack();
+            assert_eq!(updates(&frame).count(), 0);
+        }
+    }
 }
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+/// How a texture is filtered when it's drawn at a different size than its pixels.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SamplerPolicy {
+    /// Pixel-perfect, so sprites and UI stay sharp.
+    Nearest,
+
+    /// Smooths textures that are scaled up, ex. map backgrounds.
+    Linear,
+}
+
+impl SamplerPolicy {
+    fn descriptor(self) -> wgpu::SamplerDescriptor<'static> {
+        let filter = match self {
+            SamplerPolicy::Nearest => wgpu::FilterMode::Nearest,
+            SamplerPolicy::Linear => wgpu::FilterMode::Linear,
+        };
+
+        wgpu::SamplerDescriptor {
+            label: Some("atlas sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        }
+    }
+}
+
+/// Creates a sampler for each policy the first time it's used, so every atlas page drawn with the
+/// same policy shares one sampler.
+pub struct SamplerCache {
+    samplers: HashMap<SamplerPolicy, wgpu::Sampler>,
+}
+
+impl SamplerCache {
+    pub fn new() -> Self {
+        Self {
+            samplers: HashMap::new(),
+        }
+    }
+
+    pub fn get(&mut self, device: &wgpu::Device, policy: SamplerPolicy) -> &wgpu::Sampler {
+        self.samplers
+            .entry(policy)
+            .or_insert_with(|| device.create_sampler(&policy.descriptor()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_policy_filters_differently() {
+        let nearest = SamplerPolicy::Nearest.descriptor();
+        let linear = SamplerPolicy::Linear.descriptor();
+
+        assert_eq!(nearest.mag_filter, wgpu::FilterMode::Nearest);
+        assert_eq!(nearest.min_filter, wgpu::FilterMode::Nearest);
+        assert_eq!(linear.mag_filter, wgpu::FilterMode::Linear);
+        assert_eq!(linear.min_filter, wgpu::FilterMode::Linear);
+    }
+
+    #[test]
+    fn textures_are_clamped_to_their_region() {
+        for policy in [SamplerPolicy::Nearest, SamplerPolicy::Linear] {
+            let descriptor = policy.descriptor();
+
+            assert_eq!(descriptor.address_mode_u, wgpu::AddressMode::ClampToEdge);
+            assert_eq!(descriptor.address_mode_v, wgpu::AddressMode::ClampToEdge);
+        }
+    }
+}
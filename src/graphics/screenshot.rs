@@ -0,0 +1,26 @@
+use std::{
+    fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The directory screenshots are saved in, relative to the working directory.
+const SCREENSHOTS_DIR: &str = "screenshots";
+
+/// Saves a frame as a PNG in the screenshots directory, named after the time it was taken.
+pub fn save_screenshot(image: &image::RgbaImage) -> Result<PathBuf, image::ImageError> {
+    fs::create_dir_all(SCREENSHOTS_DIR)?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    let path = PathBuf::from(SCREENSHOTS_DIR).join(format!(
+        "screenshot-{}-{:03}.png",
+        timestamp.as_secs(),
+        timestamp.subsec_millis()
+    ));
+
+    image.save(&path)?;
+    Ok(path)
+}
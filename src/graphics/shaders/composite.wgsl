@@ -0,0 +1,40 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+}
+
+struct PostProcess {
+    colour_matrix: mat4x4<f32>,
+    colour_offset: vec4<f32>,
+    fade: f32,
+}
+
+@group(0) @binding(0)
+var scene: texture_2d<f32>;
+
+@group(0) @binding(1)
+var scene_sampler: sampler;
+
+@group(0) @binding(2)
+var<uniform> post_process: PostProcess;
+
+// Draws a single triangle that covers the whole screen, so no vertex buffer is needed.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.tex_coords = uv;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let scene_colour = textureSample(scene, scene_sampler, in.tex_coords);
+
+    var colour = post_process.colour_matrix * scene_colour + post_process.colour_offset;
+    colour = vec4<f32>(mix(colour.rgb, vec3<f32>(0.0), post_process.fade), colour.a);
+
+    return clamp(colour, vec4<f32>(0.0), vec4<f32>(1.0));
+}
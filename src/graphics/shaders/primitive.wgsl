@@ -0,0 +1,49 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct InstanceInput {
+    @location(2) model_transform_0: vec4<f32>,
+    @location(3) model_transform_1: vec4<f32>,
+    @location(4) model_transform_2: vec4<f32>,
+    @location(5) model_transform_3: vec4<f32>,
+    @location(6) uv_rect: vec4<f32>,
+    @location(7) tint: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) colour: vec4<f32>,
+}
+
+struct Camera {
+    view: mat4x4<f32>,
+}
+
+@group(0)
+@binding(0)
+var<uniform> camera: Camera;
+
+// Primitives are quads like everything else, but they're filled with their tint instead of being
+// textured, so the uv rect is ignored.
+@vertex
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model_transform = mat4x4<f32>(
+        instance.model_transform_0,
+        instance.model_transform_1,
+        instance.model_transform_2,
+        instance.model_transform_3,
+    );
+
+    var out: VertexOutput;
+    out.colour = instance.tint;
+    out.clip_position = camera.view * model_transform * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Blend states expect premultiplied alpha.
+    return vec4<f32>(in.colour.rgb * in.colour.a, in.colour.a);
+}
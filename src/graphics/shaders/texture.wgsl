@@ -1,28 +1,44 @@
-
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
 }
 
+struct InstanceInput {
+    @location(2) model_transform_0: vec4<f32>,
+    @location(3) model_transform_1: vec4<f32>,
+    @location(4) model_transform_2: vec4<f32>,
+    @location(5) model_transform_3: vec4<f32>,
+    @location(6) uv_rect: vec4<f32>,
+    @location(7) tint: vec4<f32>,
+}
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>,
 }
 
-struct Uniforms {
-    model_transform: mat4x4<f32>,
-    camera_view: mat4x4<f32>,
+struct Camera {
+    view: mat4x4<f32>,
 }
 
 @group(0)
 @binding(0)
-var<uniform> uniforms: Uniforms;
+var<uniform> camera: Camera;
 
 @vertex
-fn vs_main(model: VertexInput) -> VertexOutput {
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model_transform = mat4x4<f32>(
+        instance.model_transform_0,
+        instance.model_transform_1,
+        instance.model_transform_2,
+        instance.model_transform_3,
+    );
+
     var out: VertexOutput;
-    out.tex_coords = model.tex_coords;
-    out.clip_position = uniforms.camera_view * uniforms.model_transform * vec4<f32>(model.position, 1.0);
+    out.tex_coords = instance.uv_rect.xy + model.tex_coords * instance.uv_rect.zw;
+    out.tint = instance.tint;
+    out.clip_position = camera.view * model_transform * vec4<f32>(model.position, 1.0);
     return out;
 }
 
@@ -34,5 +50,8 @@ var tex_sampler: sampler;
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(tex, tex_sampler, in.tex_coords);
-}
\ No newline at end of file
+    let colour = textureSample(tex, tex_sampler, in.tex_coords) * in.tint;
+
+    // Blend states expect premultiplied alpha.
+    return vec4<f32>(colour.rgb * colour.a, colour.a);
+}
@@ -0,0 +1,137 @@
+use ultraviolet::{Mat4, Vec3};
+
+use crate::component::Colour;
+
+use super::{ClipRect, Instance, RenderLayer, Renderable, Shader, Vertex};
+
+/// A flat coloured shape drawn without a texture, ex. a text input's caret or an HP bar.
+pub struct Shape {
+    pub kind: ShapeKind,
+    pub colour: Colour,
+    layer: RenderLayer,
+
+    /// The area the shape is clipped to, ex. the box of the text input a caret is in.
+    pub clip: Option<ClipRect>,
+}
+
+/// The shapes that can be drawn, in virtual pixels.
+#[derive(Clone, Debug)]
+pub enum ShapeKind {
+    /// A filled rectangle.
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+}
+
+impl Shape {
+    pub fn new(kind: ShapeKind, colour: Colour) -> Self {
+        Self {
+            kind,
+            colour,
+            layer: RenderLayer::default(),
+            clip: None,
+        }
+    }
+
+    pub fn rect(x: f32, y: f32, width: f32, height: f32, colour: Colour) -> Self {
+        Self::new(
+            ShapeKind::Rect {
+                x,
+                y,
+                width,
+                height,
+            },
+            colour,
+        )
+    }
+
+    pub fn with_layer(mut self, layer: RenderLayer) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn with_clip(mut self, clip: ClipRect) -> Self {
+        self.clip = Some(clip);
+        self
+    }
+
+    pub fn layer(&self) -> RenderLayer {
+        self.layer
+    }
+
+    /// Gets the quads the shape is drawn with.
+    pub fn instances(&self) -> Vec<Instance> {
+        match &self.kind {
+            ShapeKind::Rect {
+                x,
+                y,
+                width,
+                height,
+            } => vec![self.rect_instance(*x, *y, *width, *height)],
+        }
+    }
+
+    fn rect_instance(&self, x: f32, y: f32, width: f32, height: f32) -> Instance {
+        let model_transform = Mat4::from_translation(Vec3 { x, y, z: 0.0 })
+            * Mat4::from_nonuniform_scale(Vec3 {
+                x: width,
+                y: height,
+                z: 1.0,
+            });
+
+        Instance::primitive(&model_transform, self.colour)
+    }
+}
+
+impl Renderable for Shape {
+    const SHADER: Shader = Shader {
+        file_name: "primitive.wgsl",
+        source: include_str!("shaders/primitive.wgsl"),
+    };
+
+    fn create_render_pipeline(
+        device: &wgpu::Device,
+        shader: &wgpu::ShaderModule,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        _texture_bind_group_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        blend_state: wgpu::BlendState,
+    ) -> wgpu::RenderPipeline {
+        // Primitives aren't textured, so only the camera is bound.
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("primitive render pipeline layout"),
+                bind_group_layouts: &[camera_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("primitive render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc(), Instance::desc()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(blend_state),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        })
+    }
+}
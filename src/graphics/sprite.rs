@@ -1,14 +1,24 @@
 use uuid::Uuid;
 
-use crate::{component::Transform, resource::AssetManager};
+use crate::{
+    component::{Colour, Transform},
+    resource::AssetManager,
+};
 
-use super::{RenderableV2, Texture};
+use super::{ClipRect, RenderLayer, RenderableV2, SamplerPolicy, Texture};
 
 // TODO: not a fan of this being in graphics, more like a game component.
 pub struct Sprite {
     id: Uuid,
     texture: Texture,
     transform: Transform,
+    layer: RenderLayer,
+
+    /// The colour the sprite is tinted with, ex. to fade it out.
+    pub colour: Colour,
+
+    /// The area the sprite is clipped to, ex. the visible part of a scrolling list.
+    pub clip: Option<ClipRect>,
 }
 
 impl Sprite {
@@ -17,6 +27,9 @@ impl Sprite {
             id: Uuid::new_v4(),
             texture: AssetManager::get_texture(nx_path).unwrap(),
             transform: Transform::default(),
+            layer: RenderLayer::default(),
+            colour: Colour::white(),
+            clip: None,
         }
     }
 
@@ -24,6 +37,18 @@ impl Sprite {
         self.transform = transform;
         self
     }
+
+    pub fn with_layer(mut self, layer: RenderLayer) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    /// Draws the sprite with `policy` instead of its layer's, ex. something crisp in a layer that's
+    /// usually smoothed.
+    pub fn with_sampler_policy(mut self, policy: SamplerPolicy) -> Self {
+        self.texture.sampler_policy = Some(policy);
+        self
+    }
 }
 
 impl RenderableV2 for Sprite {
@@ -38,4 +63,16 @@ impl RenderableV2 for Sprite {
     fn transform(&self) -> &Transform {
         &self.transform
     }
+
+    fn layer(&self) -> RenderLayer {
+        self.layer
+    }
+
+    fn colour(&self) -> Colour {
+        self.colour
+    }
+
+    fn clip(&self) -> Option<ClipRect> {
+        self.clip
+    }
 }
@@ -0,0 +1,188 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// What the renderer did to draw a frame, published back to the game thread after each frame.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    pub pipeline_switches: u32,
+    pub bind_group_switches: u32,
+
+    /// Writes to buffers, ex. the camera uniform and instances.
+    pub buffer_uploads: u32,
+
+    /// Writes to atlas pages.
+    pub texture_uploads: u32,
+
+    /// CPU time spent processing the frame's render updates.
+    pub update_time: Duration,
+
+    /// CPU time spent recording and submitting the frame.
+    pub render_time: Duration,
+
+    /// GPU time spent drawing, if the adapter supports timestamp queries.
+    /// This lags a frame or two behind, since it's read back without waiting on the GPU.
+    pub gpu_time: Option<Duration>,
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} draw calls, {} pipeline switches, {} bind group switches, {} buffer uploads, \
+             {} texture uploads, update {:?}, render {:?}",
+            self.draw_calls,
+            self.pipeline_switches,
+            self.bind_group_switches,
+            self.buffer_uploads,
+            self.texture_uploads,
+            self.update_time,
+            self.render_time,
+        )?;
+
+        if let Some(gpu_time) = self.gpu_time {
+            write!(f, ", gpu {:?}", gpu_time)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Measures how long the GPU spends drawing a frame with timestamp queries.
+///
+/// Timestamps are copied into a buffer that's mapped in the background, and frames aren't timed
+/// while a previous frame's timestamps are still being read back, so the renderer never has to
+/// wait on the GPU.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+
+    /// The number of nanoseconds per timestamp tick.
+    period: f32,
+
+    /// Whether the readback buffer is in use, ex. waiting to be mapped.
+    pending: bool,
+
+    /// Set once the readback buffer has been mapped and can be read.
+    mapped: Arc<AtomicBool>,
+}
+
+impl GpuTimer {
+    const QUERY_COUNT: u32 = 2;
+    const BUFFER_SIZE: wgpu::BufferAddress =
+        Self::QUERY_COUNT as wgpu::BufferAddress * wgpu::QUERY_SIZE as wgpu::BufferAddress;
+
+    /// Creates a timer, if the device supports timestamp queries.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("gpu timer query set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: Self::QUERY_COUNT,
+        });
+
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu timer resolve buffer"),
+            size: Self::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("gpu timer readback buffer"),
+            size: Self::BUFFER_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            period: queue.get_timestamp_period(),
+            pending: false,
+            mapped: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    /// Whether this frame can be timed.
+    pub fn is_ready(&self) -> bool {
+        !self.pending
+    }
+
+    /// Gets the timestamp writes for a render pass, where `start` and `end` are whether the pass
+    /// starts and/or ends the timed part of the frame.
+    pub fn timestamp_writes(&self, start: bool, end: bool) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: start.then_some(0),
+            end_of_pass_write_index: end.then_some(1),
+        }
+    }
+
+    /// Copies the frame's timestamps into the readback buffer.
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(
+            &self.query_set,
+            0..Self::QUERY_COUNT,
+            &self.resolve_buffer,
+            0,
+        );
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            Self::BUFFER_SIZE,
+        );
+    }
+
+    /// Starts mapping the readback buffer, once the frame has been submitted.
+    pub fn start_readback(&mut self) {
+        self.pending = true;
+
+        let mapped = self.mapped.clone();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| match result {
+                Ok(_) => mapped.store(true, Ordering::Release),
+                Err(e) => log::error!("Error mapping gpu timer buffer: {}", e),
+            });
+    }
+
+    /// Gets the last timed frame's GPU time, if it has been read back since the last call.
+    pub fn try_read(&mut self, device: &wgpu::Device) -> Option<Duration> {
+        if !self.pending {
+            return None;
+        }
+
+        device.poll(wgpu::Maintain::Poll);
+
+        if !self.mapped.swap(false, Ordering::Acquire) {
+            return None;
+        }
+
+        let timestamps: Vec<u64> = {
+            let data = self.readback_buffer.slice(..).get_mapped_range();
+            bytemuck::cast_slice(&data).to_vec()
+        };
+
+        self.readback_buffer.unmap();
+        self.pending = false;
+
+        let ticks = timestamps[1].saturating_sub(timestamps[0]);
+        Some(Duration::from_nanos(
+            (ticks as f64 * self.period as f64) as u64,
+        ))
+    }
+}
@@ -1,15 +1,32 @@
-use std::{fmt, ops::Range};
+use std::fmt;
 
 use nx_pkg4::{Node, NxError, NxNode};
 
 use crate::{
-    graphics::Vertex,
+    graphics::{Instance, Vertex},
     resource::{Font, FontCharacter},
 };
 
-use super::Renderable;
+use super::{Renderable, SamplerPolicy, Shader};
 
-const INDICES: &[u16] = &[0, 1, 3, 3, 1, 2];
+/// The order of the channels in a texture's pixel data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PixelFormat {
+    /// The "reversed" format NxBitmap data is in.
+    Bgra8,
+
+    /// The format images from the `image` crate are in, ex. font atlases.
+    Rgba8,
+}
+
+impl PixelFormat {
+    pub fn to_wgpu(self) -> wgpu::TextureFormat {
+        match self {
+            PixelFormat::Bgra8 => wgpu::TextureFormat::Bgra8UnormSrgb,
+            PixelFormat::Rgba8 => wgpu::TextureFormat::Rgba8UnormSrgb,
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Texture {
@@ -18,16 +35,26 @@ pub struct Texture {
     pub width: u32,
     pub height: u32,
     pub data: Vec<u8>,
+    pub format: PixelFormat,
 
     /// The texture origin.
     pub origin: Option<(i32, i32)>,
 
-    /// The texture layer.
-    layer: Option<i64>,
+    /// The texture's z order within its render layer.
+    pub z: Option<i64>,
 
-    pub vertex_buffer: Vec<u8>,
-    pub index_buffer: Vec<u8>,
-    pub index_buffer_range: Range<u32>,
+    /// The texture's alpha at the start and end of its frame (a0 and a1), from 0 to 255.
+    pub alpha: (u8, u8),
+
+    /// The size of the texture's quad, in pixels.
+    pub size: (f32, f32),
+
+    /// The area of `data` drawn on the quad, as (x, y, width, height) in texture coordinates.
+    /// This is remapped onto the texture's atlas page when rendering.
+    pub uv_rect: [f32; 4],
+
+    /// How the texture is filtered, or `None` to use its render layer's policy.
+    pub sampler_policy: Option<SamplerPolicy>,
 }
 
 impl Texture {
@@ -38,11 +65,27 @@ impl Texture {
             None => None,
         };
 
-        let layer = match node.get("z") {
+        let z = match node.get("z") {
             Some(child) => child.integer()?,
             None => None,
         };
 
+        // a1 is usually left out when the alpha doesn't change over the frame.
+        let a0 = match node.get("a0") {
+            Some(child) => child.integer()?,
+            None => None,
+        };
+
+        let a1 = match node.get("a1") {
+            Some(child) => child.integer()?,
+            None => None,
+        };
+
+        let alpha = (
+            clamp_alpha(a0.unwrap_or(255)),
+            clamp_alpha(a1.or(a0).unwrap_or(255)),
+        );
+
         let bitmap = match node.bitmap()? {
             Some(bitmap) => bitmap,
             None => {
@@ -53,36 +96,65 @@ impl Texture {
 
         let width = bitmap.width.into();
         let height = bitmap.height.into();
-        let vertex_buffer = get_bitmap_vertex_buffer(width, height);
 
         Ok(Some(Self {
             path: path.to_string(),
             width,
             height,
             data: bitmap.data,
+            format: PixelFormat::Bgra8,
             origin,
-            layer,
-            vertex_buffer,
-            index_buffer: bytemuck::cast_slice(INDICES).to_vec(),
-            index_buffer_range: 0..INDICES.len() as u32,
+            z,
+            alpha,
+            size: (width as f32, height as f32),
+            uv_rect: [0.0, 0.0, 1.0, 1.0],
+            sampler_policy: None,
         }))
     }
 
     pub fn font(character: &FontCharacter, font: &Font) -> Self {
-        let vertex_buffer = get_font_vertex_buffer(character, font);
+        let font_width = font.width as f32;
+        let font_height = font.height as f32;
 
         Self {
-            path: "font".to_string(),
+            path: font.texture_path.clone(),
             width: font.width,
             height: font.height,
             data: font.data.clone(), // TODO: fix this
+            format: PixelFormat::Rgba8,
             origin: None,
-            layer: None,
-            vertex_buffer,
-            index_buffer: bytemuck::cast_slice(INDICES).to_vec(),
-            index_buffer_range: 0..INDICES.len() as u32,
+            z: None,
+            alpha: (255, 255),
+            size: (character.width, character.height),
+            uv_rect: [
+                character.x.0 / font_width,
+                character.y.0 / font_height,
+                character.width / font_width,
+                character.height / font_height,
+            ],
+            sampler_policy: None,
         }
     }
+
+    /// Converts the texture's data into `format`, swapping the red and blue channels if needed.
+    pub fn into_format(mut self, format: PixelFormat) -> Self {
+        if self.format != format {
+            for pixel in self.data.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+
+            self.format = format;
+        }
+
+        self
+    }
+
+    /// Gets the texture's alpha `progress` of the way through its frame, from 0.0 to 1.0.
+    pub fn alpha_at(&self, progress: f32) -> f32 {
+        let (a0, a1) = self.alpha;
+        let alpha = a0 as f32 + (a1 as f32 - a0 as f32) * progress.clamp(0.0, 1.0);
+        alpha / 255.0
+    }
 }
 
 /// Manually implementing Debug for Texture, replacing data with an empty slice since it can
@@ -96,11 +168,13 @@ impl fmt::Debug for Texture {
             width: &'a u32,
             height: &'a u32,
             data: [u8; 0],
+            format: &'a PixelFormat,
             origin: &'a Option<(i32, i32)>,
-            layer: &'a Option<i64>,
-            vertex_buffer: [u8; 0],
-            index_buffer: [u8; 0],
-            index_buffer_range: &'a Range<u32>,
+            z: &'a Option<i64>,
+            alpha: &'a (u8, u8),
+            size: &'a (f32, f32),
+            uv_rect: &'a [f32; 4],
+            sampler_policy: &'a Option<SamplerPolicy>,
         }
 
         let Self {
@@ -108,11 +182,13 @@ impl fmt::Debug for Texture {
             width,
             height,
             data: _,
+            format,
             origin,
-            layer,
-            vertex_buffer: _,
-            index_buffer: _,
-            index_buffer_range,
+            z,
+            alpha,
+            size,
+            uv_rect,
+            sampler_policy,
         } = self;
 
         fmt::Debug::fmt(
@@ -121,11 +197,13 @@ impl fmt::Debug for Texture {
                 width,
                 height,
                 data: [],
+                format,
                 origin,
-                layer,
-                vertex_buffer: [],
-                index_buffer: [],
-                index_buffer_range,
+                z,
+                alpha,
+                size,
+                uv_rect,
+                sampler_policy,
             },
             f,
         )
@@ -133,36 +211,41 @@ impl fmt::Debug for Texture {
 }
 
 impl Renderable for Texture {
+    const SHADER: Shader = Shader {
+        file_name: "texture.wgsl",
+        source: include_str!("shaders/texture.wgsl"),
+    };
+
     fn create_render_pipeline(
         device: &wgpu::Device,
-        transform_bind_group_layout: &wgpu::BindGroupLayout,
+        shader: &wgpu::ShaderModule,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
         texture_bind_group_layout: &wgpu::BindGroupLayout,
-        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        blend_state: wgpu::BlendState,
     ) -> wgpu::RenderPipeline {
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("texture render pipeline layout"),
-                bind_group_layouts: &[transform_bind_group_layout, texture_bind_group_layout],
+                bind_group_layouts: &[camera_bind_group_layout, texture_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/texture.wgsl"));
-
         device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some("texture render pipeline"),
             layout: Some(&render_pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: shader,
                 entry_point: Some("vs_main"),
-                buffers: &[Vertex::desc()],
+                buffers: &[Vertex::desc(), Instance::desc()],
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: shader,
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    format,
+                    blend: Some(blend_state),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
@@ -171,7 +254,8 @@ impl Renderable for Texture {
                 topology: wgpu::PrimitiveTopology::TriangleList,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
+                // Mirrored quads are wound the other way, so nothing can be culled.
+                cull_mode: None,
                 polygon_mode: wgpu::PolygonMode::Fill,
                 unclipped_depth: false,
                 conservative: false,
@@ -188,54 +272,46 @@ impl Renderable for Texture {
     }
 }
 
-fn get_bitmap_vertex_buffer(width: u32, height: u32) -> Vec<u8> {
-    let width = width as f32;
-    let height = height as f32;
-
-    let vertices = [
-        Vertex {
-            position: [0.0, 0.0, 0.0],
-            tex_coords: [0.0, 0.0],
-        },
-        Vertex {
-            position: [0.0, height, 0.0],
-            tex_coords: [0.0, 1.0],
-        },
-        Vertex {
-            position: [width, height, 0.0],
-            tex_coords: [1.0, 1.0],
-        },
-        Vertex {
-            position: [width, 0.0, 0.0],
-            tex_coords: [1.0, 0.0],
-        },
-    ];
-
-    bytemuck::cast_slice(&vertices).to_vec()
+fn clamp_alpha(alpha: i64) -> u8 {
+    alpha.clamp(0, 255) as u8
 }
 
-fn get_font_vertex_buffer(character: &FontCharacter, font: &Font) -> Vec<u8> {
-    let font_width = font.width as f32;
-    let font_height = font.height as f32;
-
-    let vertices = [
-        Vertex {
-            position: [0.0, 0.0, 0.0],
-            tex_coords: [character.x.0 / font_width, character.y.0 / font_height],
-        },
-        Vertex {
-            position: [0.0, character.height, 0.0],
-            tex_coords: [character.x.0 / font_width, character.y.1 / font_height],
-        },
-        Vertex {
-            position: [character.width, character.height, 0.0],
-            tex_coords: [character.x.1 / font_width, character.y.1 / font_height],
-        },
-        Vertex {
-            position: [character.width, 0.0, 0.0],
-            tex_coords: [character.x.1 / font_width, character.y.0 / font_height],
-        },
-    ];
-
-    bytemuck::cast_slice(&vertices).to_vec()
+#[cfg(test)]
+mod tests {
+    use crate::graphics::tests::test_texture;
+
+    use super::*;
+
+    fn fading(a0: u8, a1: u8) -> Texture {
+        Texture {
+            alpha: (a0, a1),
+            ..test_texture()
+        }
+    }
+
+    #[test]
+    fn alpha_is_interpolated_over_the_frame() {
+        let texture = fading(255, 0);
+
+        assert_eq!(texture.alpha_at(0.0), 1.0);
+        assert_eq!(texture.alpha_at(0.5), 0.5);
+        assert_eq!(texture.alpha_at(1.0), 0.0);
+    }
+
+    #[test]
+    fn alpha_can_fade_in() {
+        let texture = fading(0, 255);
+
+        assert_eq!(texture.alpha_at(0.0), 0.0);
+        assert_eq!(texture.alpha_at(0.5), 0.5);
+        assert_eq!(texture.alpha_at(1.0), 1.0);
+    }
+
+    #[test]
+    fn alpha_is_clamped_outside_the_frame() {
+        let texture = fading(255, 0);
+
+        assert_eq!(texture.alpha_at(-1.0), 1.0);
+        assert_eq!(texture.alpha_at(2.0), 0.0);
+    }
 }
@@ -1,8 +1,8 @@
 use uuid::Uuid;
 
 use crate::{
-    component::Transform,
-    graphics::{RenderableV2, Texture},
+    component::{Colour, Transform},
+    graphics::{RenderLayer, RenderableV2, Texture},
     resource::AssetManager,
 };
 
@@ -16,8 +16,7 @@ pub struct Button {
 
     textures: [Option<Texture>; 4],
     transform: Transform,
-
-    pub on_click: Option<fn()>,
+    pub colour: Colour,
 }
 
 impl Button {
@@ -34,7 +33,7 @@ impl Button {
             state: ButtonState::Default,
             textures,
             transform: Transform::default(),
-            on_click: None,
+            colour: Colour::white(),
         }
     }
 
@@ -42,11 +41,6 @@ impl Button {
         self.transform = transform;
         self
     }
-
-    pub fn with_on_click(mut self, on_click: fn()) -> Self {
-        self.on_click = Some(on_click);
-        self
-    }
 }
 
 impl RenderableV2 for Button {
@@ -65,6 +59,21 @@ impl RenderableV2 for Button {
     fn transform(&self) -> &Transform {
         &self.transform
     }
+
+    fn layer(&self) -> RenderLayer {
+        RenderLayer::Ui
+    }
+
+    fn colour(&self) -> Colour {
+        // Grey out disabled buttons that don't have their own disabled texture.
+        if self.state == ButtonState::Disabled
+            && self.textures[ButtonState::Disabled as usize].is_none()
+        {
+            return Colour::rgb(128, 128, 128).with_alpha(self.colour.alpha);
+        }
+
+        self.colour
+    }
 }
 
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -1,8 +1,8 @@
 use uuid::Uuid;
 
 use crate::{
-    component::Transform,
-    graphics::{RenderableV2, Texture},
+    component::{Colour, Transform},
+    graphics::{ClipRect, RenderLayer, RenderableV2, Texture},
     resource::{Font, FontCharacter},
 };
 
@@ -11,6 +11,8 @@ pub struct Text {
     id: Uuid,
     texture: Texture,
     transform: Transform,
+    pub colour: Colour,
+    clip: Option<ClipRect>,
 }
 
 impl Text {
@@ -19,6 +21,8 @@ impl Text {
             id: Uuid::new_v4(),
             texture: Texture::font(character, font),
             transform: Transform::default(),
+            colour: Colour::white(),
+            clip: None,
         }
     }
 
@@ -26,6 +30,11 @@ impl Text {
         self.transform = transform;
         self
     }
+
+    pub fn with_clip(mut self, clip: ClipRect) -> Self {
+        self.clip = Some(clip);
+        self
+    }
 }
 
 impl RenderableV2 for Text {
@@ -40,4 +49,16 @@ impl RenderableV2 for Text {
     fn transform(&self) -> &Transform {
         &self.transform
     }
+
+    fn layer(&self) -> RenderLayer {
+        RenderLayer::Ui
+    }
+
+    fn colour(&self) -> Colour {
+        self.colour
+    }
+
+    fn clip(&self) -> Option<ClipRect> {
+        self.clip
+    }
 }
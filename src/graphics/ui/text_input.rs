@@ -1,6 +1,6 @@
 use uuid::Uuid;
 
-use crate::{component::Transform, resource::FontDescriptor};
+use crate::{component::Transform, graphics::ClipRect, resource::FontDescriptor};
 
 // TODO: placeholder text?
 // TODO: font size
@@ -41,4 +41,14 @@ impl TextInput {
         self.transform = transform;
         self
     }
+
+    /// Gets the input's box, which its text is clipped to.
+    pub fn clip_rect(&self) -> ClipRect {
+        ClipRect::new(
+            self.transform.x,
+            self.transform.y,
+            self.width as f32,
+            self.height as f32,
+        )
+    }
 }
@@ -0,0 +1,141 @@
+use std::str::FromStr;
+
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+
+use crate::settings::Settings;
+
+/// Maps the game's fixed virtual resolution onto the window.
+///
+/// The game is always laid out and drawn at the virtual resolution, then scaled up to fit the
+/// window without changing its aspect ratio. Any leftover space is filled with black bars.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Viewport {
+    pub virtual_size: PhysicalSize<u32>,
+    pub scaling: ScalingMode,
+}
+
+impl Viewport {
+    pub fn new(width: u32, height: u32, scaling: ScalingMode) -> Self {
+        Self {
+            virtual_size: PhysicalSize::new(width, height),
+            scaling,
+        }
+    }
+
+    /// Reads the virtual resolution (ex. `resolution = 1024x768`) and scaling mode
+    /// (`scaling = letterbox` or `scaling = integer`) from the settings.
+    pub fn from_settings(settings: &Settings) -> Self {
+        let default = Self::default();
+        let resolution: String = settings.get_or("resolution", String::new());
+
+        let virtual_size = match parse_resolution(&resolution) {
+            Some(size) => size,
+            None => {
+                if !resolution.is_empty() {
+                    log::warn!("Invalid resolution {}, using the default", resolution);
+                }
+
+                default.virtual_size
+            }
+        };
+
+        Self {
+            virtual_size,
+            scaling: settings.get_or("scaling", default.scaling),
+        }
+    }
+
+    /// Gets the number of physical pixels per virtual pixel in a frame of `frame_size`.
+    pub fn scale(self, frame_size: PhysicalSize<u32>) -> f32 {
+        let scale = f32::min(
+            frame_size.width as f32 / self.virtual_size.width as f32,
+            frame_size.height as f32 / self.virtual_size.height as f32,
+        );
+
+        match self.scaling {
+            ScalingMode::Letterbox => scale,
+            // Fall back to scaling down when the frame is smaller than the virtual resolution.
+            ScalingMode::Integer if scale >= 1.0 => scale.floor(),
+            ScalingMode::Integer => scale,
+        }
+    }
+
+    /// Gets the area of the frame the game is drawn in, as (x, y, width, height) in physical
+    /// pixels, centered in the frame.
+    pub fn frame_rect(self, frame_size: PhysicalSize<u32>) -> [f32; 4] {
+        let scale = self.scale(frame_size);
+        let width = self.virtual_size.width as f32 * scale;
+        let height = self.virtual_size.height as f32 * scale;
+
+        [
+            ((frame_size.width as f32 - width) / 2.0).floor(),
+            ((frame_size.height as f32 - height) / 2.0).floor(),
+            width,
+            height,
+        ]
+    }
+
+    /// Maps a position in the window, ex. the cursor's, into virtual coordinates.
+    pub fn to_virtual(
+        self,
+        position: PhysicalPosition<f64>,
+        window_size: PhysicalSize<u32>,
+    ) -> (f64, f64) {
+        let [x, y, _, _] = self.frame_rect(window_size);
+        let scale = self.scale(window_size) as f64;
+
+        (
+            (position.x - x as f64) / scale,
+            (position.y - y as f64) / scale,
+        )
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(800, 600, ScalingMode::Letterbox)
+    }
+}
+
+/// How the virtual resolution is scaled up to fit the window.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScalingMode {
+    /// Scale as much as possible while keeping the aspect ratio, smoothing the result.
+    Letterbox,
+
+    /// Only scale by whole numbers, so every virtual pixel is the same size on screen.
+    Integer,
+}
+
+impl ScalingMode {
+    pub fn filter_mode(self) -> wgpu::FilterMode {
+        match self {
+            ScalingMode::Letterbox => wgpu::FilterMode::Linear,
+            ScalingMode::Integer => wgpu::FilterMode::Nearest,
+        }
+    }
+}
+
+impl FromStr for ScalingMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "letterbox" => Ok(ScalingMode::Letterbox),
+            "integer" => Ok(ScalingMode::Integer),
+            _ => Err(()),
+        }
+    }
+}
+
+fn parse_resolution(resolution: &str) -> Option<PhysicalSize<u32>> {
+    let (width, height) = resolution.split_once('x')?;
+    let width = width.trim().parse().ok()?;
+    let height = height.trim().parse().ok()?;
+
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    Some(PhysicalSize::new(width, height))
+}
@@ -1,35 +1,67 @@
 use std::{
-    collections::HashMap,
+    any::Any,
+    collections::{HashMap, VecDeque},
+    panic,
     sync::{mpsc, Arc},
     thread,
     time::{Duration, Instant},
 };
 
 use component::Camera;
-use graphics::{Renderer, RendererEvent, RendererManager};
-use resource::{input::CursorState, AssetManager, Cursor, WindowProxy};
+use event::{ButtonClicked, KeyPressed, TextSubmitted};
+use graphics::{
+    extract_renderables, extract_shapes,
+    ui::{Button, Text},
+    Extractor, FrameAck, RenderStats, Renderer, RendererEvent, RendererManager, RendererOptions,
+    Sprite, Viewport,
+};
+use resource::{input::CursorState, AssetManager, Cursor, ScreenEffects, WindowProxy};
 use scene::{LoginScene, Scene};
+use settings::Settings;
 use state::State;
 use winit::{
     application::ApplicationHandler,
-    dpi::LogicalSize,
-    event::WindowEvent,
-    event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    dpi::{LogicalSize, PhysicalSize},
+    event::{ElementState, WindowEvent},
+    event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy},
+    keyboard::{KeyCode, PhysicalKey},
     window::{CustomCursor, Window, WindowId},
 };
 
 mod component;
+mod entity;
+mod event;
 mod graphics;
 mod resource;
 mod scene;
+mod settings;
 mod state;
 mod system;
 
+/// The number of events that can be queued for the renderer before the game thread has to wait.
+const RENDERER_CHANNEL_BOUND: usize = 8;
+
+/// How long the screen takes to fade out, and then back in, when the scene changes.
+const SCENE_FADE_DURATION: Duration = Duration::from_millis(300);
+
 enum WindowState {
-    Uninitialized,
+    Uninitialized {
+        proxy: EventLoopProxy<AppEvent>,
+        renderer_options: RendererOptions,
+    },
     Initialized(WindowManager),
 }
 
+/// Status reported to the event loop by the renderer and game threads.
+enum AppEvent {
+    /// A thread stopped because of an error it couldn't recover from, or a panic.
+    /// The game can't keep running without it, so the event loop exits.
+    ThreadExited {
+        thread: &'static str,
+        reason: String,
+    },
+}
+
 struct WindowManager {
     sender: mpsc::Sender<WindowEvent>,
 }
@@ -38,43 +70,68 @@ struct Cedar {
     window: Arc<Window>,
     state: State,
     systems: Vec<fn(&mut State)>,
-    scene: Box<dyn Scene>,
-    renderer_tx: mpsc::Sender<RendererEvent>,
+    scene: Option<Box<dyn Scene>>,
+
+    /// The scene being switched to once the screen has faded out.
+    next_scene: Option<Box<dyn Scene>>,
+
+    renderer_tx: mpsc::SyncSender<RendererEvent>,
+
+    /// Events that didn't fit in the renderer's channel, sent once there's room again so the game
+    /// thread never waits on the renderer.
+    unsent_renderer_events: VecDeque<RendererEvent>,
+
+    renderer_manager: RendererManager,
     window_rx: mpsc::Receiver<WindowEvent>,
+    stats_rx: mpsc::Receiver<RenderStats>,
     custom_cursors: HashMap<CursorState, CustomCursor>,
+    viewport: Viewport,
 }
 
 impl Cedar {
     fn run(mut self) {
         self.init();
 
-        let mut renderer_manager = RendererManager::new(self.renderer_tx.clone());
-
         let mut limiter = FrameLimiter::new(60);
         let mut rendered_frames = 0;
         let mut rendered_frames_tracker = Instant::now();
 
         loop {
             if limiter.ready_for_update() {
+                self.state.update_events();
                 self.handle_window_events();
+                self.send_renderer_events();
 
                 for system in self.systems.iter() {
                     (system)(&mut self.state);
                 }
 
+                if let Some(scene) = self.scene.as_mut() {
+                    scene.update(&mut self.state);
+                }
+
+                self.update_scene();
+
                 self.update_cursor_icon();
                 limiter.last_update_start = Instant::now();
             }
 
             if limiter.ready_for_frame() {
-                renderer_manager.generate_and_send_events(&mut self.state);
+                self.renderer_manager
+                    .generate_and_send_events(&mut self.state);
 
                 limiter.last_frame_start = Instant::now();
                 rendered_frames += 1;
             }
 
+            self.receive_render_stats();
+
             if rendered_frames_tracker.elapsed() >= Duration::from_secs(1) {
-                log::info!("rendered {} frames!", rendered_frames);
+                log::info!(
+                    "rendered {} frames! last frame: {}",
+                    rendered_frames,
+                    *self.state.get_resource::<RenderStats>().unwrap()
+                );
                 rendered_frames = 0;
                 rendered_frames_tracker = Instant::now();
             }
@@ -87,48 +144,89 @@ impl Cedar {
     }
 
     fn init(&mut self) {
-        let logical_window_size = self
-            .window
-            .inner_size()
-            .to_logical(self.window.scale_factor());
-
-        self.state
-            .insert_resource(Camera::new(
-                logical_window_size.width,
-                logical_window_size.height,
-            ))
-            .insert_resource(Cursor::new())
-            .insert_resource(WindowProxy::new(
-                self.window.inner_size(),
-                self.window.scale_factor(),
-            ));
-
-        self.systems.push(system::ui::button_system);
-        self.systems.push(system::ui::text_system);
-
-        self.scene.init(&mut self.state);
+        insert_default_resources(
+            &mut self.state,
+            self.viewport,
+            self.window.inner_size(),
+            self.window.scale_factor(),
+        );
+
+        self.systems.append(&mut default_systems());
+
+        for extractor in default_extractors() {
+            self.renderer_manager.register_extractor(extractor);
+        }
+
+        // Start from black, so the first scene fades in like any other.
+        self.state.screen_effects().fade_out(Duration::ZERO);
+        self.state.change_scene(LoginScene::default());
+    }
+
+    /// Fades out when a scene change is requested, then switches scenes and fades back in.
+    fn update_scene(&mut self) {
+        if let Some(scene) = self.state.next_scene.take() {
+            if self.scene.is_some() {
+                self.state.screen_effects().fade_out(SCENE_FADE_DURATION);
+            }
+
+            self.next_scene = Some(scene);
+        }
+
+        if self.state.screen_effects().is_transitioning() {
+            return;
+        }
+
+        if let Some(scene) = self.next_scene.take() {
+            self.change_scene(scene);
+            self.state.screen_effects().fade_in(SCENE_FADE_DURATION);
+        }
+    }
+
+    /// Exits the current scene, despawning everything it spawned, and enters `scene`.
+    /// The renderer releases the despawned entities' resources on the next frame.
+    fn change_scene(&mut self, mut scene: Box<dyn Scene>) {
+        if let Some(mut previous) = self.scene.take() {
+            previous.exit(&mut self.state);
+            self.state.entities.despawn_scene(previous.name());
+        }
+
+        self.state.entities.set_scene(Some(scene.name()));
+        scene.init(&mut self.state);
+        self.scene = Some(scene);
     }
 
     /// Handle any events sent from the ui thread.
     /// This immediately returns if no events are in the channel.
-    fn handle_window_events(&self) {
+    fn handle_window_events(&mut self) {
         while let Ok(event) = self.window_rx.try_recv() {
             match event {
                 WindowEvent::CursorMoved { position, .. } => {
-                    // Since `position` is a `PhysicalPosition`, we need to apply the current scale
-                    // factor to get the `LogicalPosition`.
-                    let scale_factor = self.state.window().scale_factor;
-                    self.state
-                        .cursor()
-                        .set_position(position.x / scale_factor, position.y / scale_factor);
+                    // Since `position` is a `PhysicalPosition` in the window, it has to be mapped
+                    // into virtual coordinates to line up with what's drawn.
+                    let window_size = self.state.window().inner_size;
+                    let (x, y) = self.viewport.to_virtual(position, window_size);
+                    self.state.cursor().set_position(x, y);
                 }
                 WindowEvent::MouseInput { button, state, .. } => {
-                    self.state.cursor().add_event(button, state);
+                    self.state.cursor().set_button_state(button, state);
+                }
+                WindowEvent::KeyboardInput { event, .. }
+                    // Some platforms only report PrintScreen being released, not pressed.
+                    if event.physical_key == PhysicalKey::Code(KeyCode::PrintScreen)
+                        && event.state == ElementState::Released =>
+                {
+                    self.unsent_renderer_events
+                        .push_back(RendererEvent::Screenshot);
+                }
+                WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                    self.state.send_event(KeyPressed(event));
                 }
                 WindowEvent::Resized(new_size) => {
-                    if let Err(e) = self.renderer_tx.send(RendererEvent::Resize(new_size)) {
-                        log::error!("Error sending resize event to renderer: {}", e);
-                    }
+                    // Only the newest size matters if older ones haven't been sent yet.
+                    self.unsent_renderer_events
+                        .retain(|event| !matches!(event, RendererEvent::Resize(_)));
+                    self.unsent_renderer_events
+                        .push_back(RendererEvent::Resize(new_size));
 
                     self.state
                         .window()
@@ -139,6 +237,31 @@ impl Cedar {
         }
     }
 
+    /// Sends as many unsent events to the renderer as its channel has room for, keeping the rest
+    /// for the next update.
+    fn send_renderer_events(&mut self) {
+        while let Some(event) = self.unsent_renderer_events.pop_front() {
+            match self.renderer_tx.try_send(event) {
+                Ok(_) => {}
+                Err(mpsc::TrySendError::Full(event)) => {
+                    self.unsent_renderer_events.push_front(event);
+                    break;
+                }
+                Err(e) => {
+                    log::error!("Error sending event to renderer: {}", e);
+                    self.unsent_renderer_events.clear();
+                }
+            }
+        }
+    }
+
+    /// Keeps the `RenderStats` resource up to date with the last frame the renderer finished.
+    fn receive_render_stats(&self) {
+        if let Some(stats) = self.stats_rx.try_iter().last() {
+            *self.state.get_resource_mut::<RenderStats>().unwrap() = stats;
+        }
+    }
+
     fn update_cursor_icon(&self) {
         let mut cursor = self.state.cursor();
 
@@ -158,13 +281,20 @@ impl Cedar {
     }
 }
 
-impl ApplicationHandler for WindowState {
+impl ApplicationHandler<AppEvent> for WindowState {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         match self {
-            WindowState::Uninitialized => {
+            WindowState::Uninitialized {
+                proxy,
+                renderer_options,
+            } => {
+                let proxy = proxy.clone();
+
+                let virtual_size = renderer_options.viewport.virtual_size;
+
                 let window_attributes = Window::default_attributes()
                     .with_title("CedarMS")
-                    .with_inner_size(LogicalSize::new(800, 600));
+                    .with_inner_size(LogicalSize::new(virtual_size.width, virtual_size.height));
 
                 let window = Arc::new(
                     event_loop
@@ -175,13 +305,26 @@ impl ApplicationHandler for WindowState {
                 // Initialize the renderer passing it the event receiver.
                 // The channel is used for other components to send updates directly to the renderer,
                 // ex. an entity was added to the world to be rendered, an asset was registered, etc.
-                let (renderer_tx, renderer_rx) = mpsc::channel::<RendererEvent>();
-                let renderer =
-                    futures::executor::block_on(Renderer::new(window.clone(), renderer_rx));
+                // The channel is bounded so the game thread can't get too far ahead of the renderer.
+                let (renderer_tx, renderer_rx) =
+                    mpsc::sync_channel::<RendererEvent>(RENDERER_CHANNEL_BOUND);
+                let (ack_tx, ack_rx) = mpsc::channel::<FrameAck>();
+                let (stats_tx, stats_rx) = mpsc::channel::<RenderStats>();
+                let viewport = renderer_options.viewport;
+                let max_frames_in_flight = renderer_options.max_frames_in_flight;
+                let renderer = futures::executor::block_on(Renderer::new(
+                    window.clone(),
+                    renderer_rx,
+                    ack_tx,
+                    renderer_options,
+                ))
+                .with_stats_sender(stats_tx);
 
                 // Start a new thread for the renderer.
                 // NOTE: creating the renderer must be done on the main thread.
-                thread::spawn(move || renderer.run());
+                spawn_monitored("renderer", proxy.clone(), move || {
+                    renderer.run().map_err(|e| e.to_string())
+                });
 
                 let (window_tx, window_rx) = mpsc::channel::<WindowEvent>();
 
@@ -203,18 +346,28 @@ impl ApplicationHandler for WindowState {
                 );
 
                 // Create and run the main game loop.
-                thread::spawn(move || {
+                spawn_monitored("game", proxy, move || {
                     let cedar = Cedar {
                         window: window.clone(),
                         state: State::new(),
                         systems: Vec::new(),
-                        scene: Box::new(LoginScene::default()),
+                        scene: None,
+                        next_scene: None,
+                        renderer_manager: RendererManager::new(
+                            renderer_tx.clone(),
+                            ack_rx,
+                            max_frames_in_flight,
+                        ),
                         renderer_tx,
+                        unsent_renderer_events: VecDeque::new(),
                         window_rx: window_rx,
+                        stats_rx,
                         custom_cursors,
+                        viewport,
                     };
 
                     cedar.run();
+                    Ok(())
                 });
 
                 let manager = WindowManager { sender: window_tx };
@@ -231,7 +384,7 @@ impl ApplicationHandler for WindowState {
         event: WindowEvent,
     ) {
         let manager = match self {
-            WindowState::Uninitialized => return,
+            WindowState::Uninitialized { .. } => return,
             WindowState::Initialized(manager) => manager,
         };
 
@@ -246,6 +399,134 @@ impl ApplicationHandler for WindowState {
             }
         }
     }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: AppEvent) {
+        match event {
+            AppEvent::ThreadExited { thread, reason } => {
+                log::error!("The {} thread stopped ({}), exiting", thread, reason);
+                event_loop.exit();
+            }
+        }
+    }
+}
+
+/// Spawns a named thread that reports to the event loop if it returns an error or panics, so the
+/// app exits instead of leaving a frozen window behind.
+fn spawn_monitored<F>(name: &'static str, proxy: EventLoopProxy<AppEvent>, f: F)
+where
+    F: FnOnce() -> Result<(), String> + Send + 'static,
+{
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || {
+            let reason = match panic::catch_unwind(panic::AssertUnwindSafe(f)) {
+                Ok(Ok(())) => return,
+                Ok(Err(e)) => e,
+                Err(payload) => format!("panicked: {}", panic_message(payload.as_ref())),
+            };
+
+            // This only fails if the event loop has already exited, so there is no one left to tell.
+            let _ = proxy.send_event(AppEvent::ThreadExited {
+                thread: name,
+                reason,
+            });
+        })
+        .expect("thread should be spawned");
+}
+
+/// Gets the message a thread panicked with, which is either a `&str` or a `String`.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+fn insert_default_resources(
+    state: &mut State,
+    viewport: Viewport,
+    window_size: PhysicalSize<u32>,
+    scale_factor: f64,
+) {
+    // The game is always laid out at the virtual resolution, regardless of the window's size.
+    let virtual_size = viewport.virtual_size;
+
+    state
+        .insert_resource(Camera::new(
+            virtual_size.width as f32,
+            virtual_size.height as f32,
+        ))
+        .insert_resource(Cursor::new())
+        .insert_resource(ScreenEffects::new())
+        .insert_resource(RenderStats::default())
+        .insert_resource(WindowProxy::new(window_size, scale_factor))
+        .add_event::<ButtonClicked>()
+        .add_event::<TextSubmitted>()
+        .add_event::<KeyPressed>();
+}
+
+fn default_systems() -> Vec<fn(&mut State)> {
+    vec![system::ui::button_system, system::ui::text_system]
+}
+
+fn default_extractors() -> Vec<Extractor> {
+    vec![
+        extract_renderables::<Sprite>,
+        extract_renderables::<Button>,
+        extract_renderables::<Text>,
+        extract_shapes,
+    ]
+}
+
+/// Renders the first frame of the login scene with a headless renderer and saves it as a PNG.
+/// This doesn't need a window, so it can be used to generate and compare golden images in CI.
+fn snapshot(path: &str) {
+    let size = PhysicalSize::new(800, 600);
+
+    let (renderer_tx, renderer_rx) = mpsc::sync_channel::<RendererEvent>(RENDERER_CHANNEL_BOUND);
+    let (ack_tx, ack_rx) = mpsc::channel::<FrameAck>();
+    let mut renderer = futures::executor::block_on(Renderer::new_headless(
+        size.width,
+        size.height,
+        renderer_rx,
+        ack_tx,
+    ));
+
+    let mut state = State::new();
+    insert_default_resources(&mut state, Viewport::default(), size, 1.0);
+    LoginScene::default().init(&mut state);
+
+    // Listed here rather than using `default_systems`, so new systems don't change snapshots
+    // until they're added on purpose.
+    let systems: [fn(&mut State); 2] = [system::ui::button_system, system::ui::text_system];
+
+    for system in systems {
+        (system)(&mut state);
+    }
+
+    let max_frames_in_flight = RendererOptions::default().max_frames_in_flight;
+    let mut renderer_manager = RendererManager::new(renderer_tx, ack_rx, max_frames_in_flight);
+
+    for extractor in default_extractors() {
+        renderer_manager.register_extractor(extractor);
+    }
+
+    renderer_manager.generate_and_send_events(&mut state);
+
+    if let Err(e) = renderer.process_pending_events() {
+        log::error!("Error rendering snapshot: {}", e);
+        return;
+    }
+
+    let frame = renderer
+        .read_frame()
+        .expect("headless renderer should have a frame");
+
+    match frame.save(path) {
+        Ok(_) => log::info!("Saved snapshot to {}", path),
+        Err(e) => log::error!("Error saving snapshot to {}: {}", path, e),
+    }
 }
 
 fn main() {
@@ -253,11 +534,28 @@ fn main() {
         .with_max_level(tracing::Level::INFO)
         .init();
 
-    let event_loop = EventLoop::new().expect("event loop should be created");
+    // `--snapshot <path>` renders the login scene without a window and exits.
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(i) = args.iter().position(|arg| arg == "--snapshot") {
+        let path = args.get(i + 1).map_or("snapshot.png", String::as_str);
+        snapshot(path);
+        return;
+    }
+
+    let event_loop = EventLoop::<AppEvent>::with_user_event()
+        .build()
+        .expect("event loop should be created");
     event_loop.set_control_flow(ControlFlow::Wait);
 
+    let settings = Settings::load();
+
+    let mut window_state = WindowState::Uninitialized {
+        proxy: event_loop.create_proxy(),
+        renderer_options: RendererOptions::from_settings(&settings),
+    };
+
     event_loop
-        .run_app(&mut WindowState::Uninitialized)
+        .run_app(&mut window_state)
         .expect("event loop should run");
 }
 
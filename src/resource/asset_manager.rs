@@ -2,7 +2,10 @@ use std::{collections::HashMap, path::Path, sync::LazyLock};
 
 use nx_pkg4::{Node, NxFile};
 
-use crate::{component::Colour, graphics::Texture};
+use crate::{
+    component::Colour,
+    graphics::{PixelFormat, Texture},
+};
 
 use super::{Font, FontDescriptor};
 
@@ -64,16 +67,7 @@ impl AssetManager {
     }
 
     pub fn get_texture_rgba(path: &str) -> Option<Texture> {
-        let mut texture = match Self::get_texture(path) {
-            Some(texture) => texture,
-            None => return None,
-        };
-
-        for pixel in texture.data.chunks_exact_mut(4) {
-            pixel.swap(0, 2);
-        }
-
-        Some(texture)
+        Self::get_texture(path).map(|texture| texture.into_format(PixelFormat::Rgba8))
     }
 
     pub fn get_font(descriptor: &FontDescriptor) -> Option<&'static Font> {
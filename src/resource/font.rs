@@ -24,6 +24,26 @@ impl FontDescriptor {
             colour,
         }
     }
+
+    pub fn colour(&self) -> Colour {
+        self.colour
+    }
+
+    /// Gets the path the font's texture is uploaded under, so each font gets its own region of
+    /// the atlas.
+    pub fn texture_path(&self) -> String {
+        let Colour {
+            red,
+            green,
+            blue,
+            alpha,
+        } = self.colour;
+
+        format!(
+            "fonts/{}/{}/{:02x}{:02x}{:02x}{:02x}",
+            self.name, self.size, red, green, blue, alpha
+        )
+    }
 }
 
 impl Default for FontDescriptor {
@@ -37,6 +57,9 @@ impl Default for FontDescriptor {
 }
 
 pub struct Font {
+    /// The path the font's texture is uploaded under, see `FontDescriptor::texture_path`.
+    pub texture_path: String,
+
     pub data: Vec<u8>,
     pub width: u32,
     pub height: u32,
@@ -46,6 +69,7 @@ pub struct Font {
 
 impl Font {
     pub fn load(descriptor: FontDescriptor) -> Self {
+        let texture_path = descriptor.texture_path();
         let path = format!("assets/fonts/{}.ttf", descriptor.name);
         let mut file = File::open(Path::new(&path)).expect("font should exist in assets/fonts");
         let mut font_bytes = Vec::new();
@@ -111,6 +135,7 @@ impl Font {
         }
 
         Self {
+            texture_path,
             data: image.to_vec(),
             width: glyphs_width + 40,
             height: glyphs_height + 40,
@@ -19,7 +19,6 @@ pub struct Cursor {
     pub state_changed: bool,
 
     pressed_buttons: HashSet<MouseButton>,
-    events: Vec<(MouseButton, ElementState)>,
 
     /// The instant when the cursor was last moved.
     last_moved: Instant,
@@ -34,7 +33,6 @@ impl Cursor {
             // This should be initially set to true to ensure we set the default idle icon.
             state_changed: true,
             pressed_buttons: HashSet::new(),
-            events: Vec::new(),
             last_moved: Instant::now(),
         }
     }
@@ -64,13 +62,11 @@ impl Cursor {
         self.state = state;
     }
 
-    pub fn add_event(&mut self, button: MouseButton, state: ElementState) {
+    pub fn set_button_state(&mut self, button: MouseButton, state: ElementState) {
         match state {
             ElementState::Pressed => self.pressed_buttons.insert(button),
             ElementState::Released => self.pressed_buttons.remove(&button),
         };
-
-        self.events.push((button, state));
     }
 
     /// Whether the cursor should be hidden.
@@ -3,9 +3,12 @@ pub use self::font::Font;
 pub use self::font::FontCharacter;
 pub use self::font::FontDescriptor;
 pub use self::input::Cursor;
+pub use self::screen_effects::ColourFilter;
+pub use self::screen_effects::ScreenEffects;
 pub use self::window_proxy::WindowProxy;
 
 mod asset_manager;
 mod font;
 pub mod input;
+mod screen_effects;
 mod window_proxy;
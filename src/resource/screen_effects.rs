@@ -0,0 +1,150 @@
+use std::time::{Duration, Instant};
+
+/// Effects applied to the whole screen after everything else is drawn, ex. fading to black
+/// between scenes and maps.
+pub struct ScreenEffects {
+    /// How far the screen is faded to black, from 0 (not at all) to 1 (completely black).
+    fade: Transition,
+
+    pub colour_filter: ColourFilter,
+}
+
+impl ScreenEffects {
+    pub fn new() -> Self {
+        Self {
+            fade: Transition::constant(0.0),
+            colour_filter: ColourFilter::identity(),
+        }
+    }
+
+    /// Fades the screen to black over `duration`, starting from the current fade.
+    pub fn fade_out(&mut self, duration: Duration) {
+        self.fade = Transition::new(self.fade.value(), 1.0, duration);
+    }
+
+    /// Fades the screen back in from black over `duration`, starting from the current fade.
+    pub fn fade_in(&mut self, duration: Duration) {
+        self.fade = Transition::new(self.fade.value(), 0.0, duration);
+    }
+
+    pub fn fade_amount(&self) -> f32 {
+        self.fade.value()
+    }
+
+    /// Whether a fade is still in progress.
+    pub fn is_transitioning(&self) -> bool {
+        !self.fade.is_finished()
+    }
+}
+
+/// A value moving linearly between two points over time.
+#[derive(Copy, Clone, Debug)]
+struct Transition {
+    from: f32,
+    to: f32,
+    start: Instant,
+    duration: Duration,
+}
+
+impl Transition {
+    fn new(from: f32, to: f32, duration: Duration) -> Self {
+        Self {
+            from,
+            to,
+            start: Instant::now(),
+            duration,
+        }
+    }
+
+    fn constant(value: f32) -> Self {
+        Self::new(value, value, Duration::ZERO)
+    }
+
+    fn value(&self) -> f32 {
+        self.value_after(self.start.elapsed())
+    }
+
+    fn is_finished(&self) -> bool {
+        self.start.elapsed() >= self.duration
+    }
+
+    /// Gets the value `elapsed` after the transition started.
+    fn value_after(&self, elapsed: Duration) -> f32 {
+        if elapsed >= self.duration {
+            return self.to;
+        }
+
+        let progress = elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        self.from + (self.to - self.from) * progress
+    }
+}
+
+/// Transforms the colour of every pixel on the screen.
+///
+/// Each row of `matrix` computes one channel of the output from the input's RGBA channels, and
+/// `offset` is added afterwards.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ColourFilter {
+    pub matrix: [[f32; 4]; 4],
+    pub offset: [f32; 4],
+}
+
+impl ColourFilter {
+    /// Leaves colours unchanged.
+    pub fn identity() -> Self {
+        Self {
+            matrix: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+            offset: [0.0; 4],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transitions_start_at_from_and_end_at_to() {
+        let transition = Transition::new(1.0, 0.0, Duration::from_millis(300));
+
+        assert_eq!(transition.value_after(Duration::ZERO), 1.0);
+        assert_eq!(transition.value_after(Duration::from_millis(300)), 0.0);
+        assert_eq!(transition.value_after(Duration::from_secs(10)), 0.0);
+    }
+
+    #[test]
+    fn transitions_move_linearly() {
+        let transition = Transition::new(0.0, 1.0, Duration::from_millis(400));
+
+        assert_eq!(transition.value_after(Duration::from_millis(100)), 0.25);
+        assert_eq!(transition.value_after(Duration::from_millis(200)), 0.5);
+        assert_eq!(transition.value_after(Duration::from_millis(300)), 0.75);
+    }
+
+    #[test]
+    fn constant_transitions_are_already_finished() {
+        let transition = Transition::constant(0.5);
+
+        assert!(transition.is_finished());
+        assert_eq!(transition.value(), 0.5);
+    }
+
+    #[test]
+    fn fades_start_from_the_current_fade() {
+        let mut effects = ScreenEffects::new();
+
+        effects.fade_out(Duration::ZERO);
+        assert_eq!(effects.fade_amount(), 1.0);
+        assert!(!effects.is_transitioning());
+
+        effects.fade_in(Duration::from_secs(60));
+        assert_eq!(effects.fade.from, 1.0);
+        assert_eq!(effects.fade.to, 0.0);
+        assert!(effects.is_transitioning());
+    }
+}
@@ -1,96 +1,187 @@
+use std::collections::HashMap;
+
 use crate::{
     component::{Colour, Transform},
+    entity::Entity,
+    event::{ButtonClicked, EventReader, TextSubmitted},
     graphics::{
         ui::{Button, TextInput},
-        Sprite,
+        RenderLayer, SamplerPolicy, Sprite,
     },
     resource::FontDescriptor,
     state::State,
 };
 
 pub trait Scene {
+    /// Identifies the scene's entities, so they can be despawned when it exits.
+    fn name(&self) -> &'static str;
+
     fn init(&mut self, _state: &mut State) {}
+
+    /// Called before the scene's entities are despawned, ex. to save anything they hold.
+    fn exit(&mut self, _state: &mut State) {}
+
+    /// Called every update after the systems run, ex. to react to their events.
+    fn update(&mut self, _state: &mut State) {}
 }
 
 #[derive(Default)]
-pub struct LoginScene;
+pub struct LoginScene {
+    buttons: HashMap<Entity, LoginButton>,
+    username_input: Option<Entity>,
+    button_clicks: EventReader<ButtonClicked>,
+    submitted_text: EventReader<TextSubmitted>,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum LoginButton {
+    Login,
+    SaveLoginId,
+    FindLoginId,
+    FindPassword,
+    Join,
+    Website,
+    Exit,
+}
 
 impl Scene for LoginScene {
+    fn name(&self) -> &'static str {
+        "login"
+    }
+
     fn init(&mut self, state: &mut State) {
         init_sprites(state);
-        init_buttons(state);
-        init_text_inputs(state);
+        self.buttons = init_buttons(state);
+        self.username_input = Some(init_text_inputs(state));
+    }
+
+    fn update(&mut self, state: &mut State) {
+        let mut username = None;
+
+        for ButtonClicked(button) in self.button_clicks.read(&state.events()) {
+            match self.buttons.get(button) {
+                Some(LoginButton::Login) => username = Some(typed_text(state, self.username_input)),
+                Some(LoginButton::Exit) => std::process::exit(0),
+                Some(button) => log::info!("{:?} clicked", button),
+                None => {}
+            }
+        }
+
+        // Pressing enter in the username input logs in too.
+        for TextSubmitted { input, text } in self.submitted_text.read(&state.events()) {
+            if Some(*input) == self.username_input {
+                username = Some(text.clone());
+            }
+        }
+
+        // TODO: actually log in once there's a server to talk to.
+        if let Some(username) = username {
+            log::info!("Logging in as {}", username);
+        }
     }
 }
 
+/// Gets the text typed in a text input, or nothing if it's gone.
+fn typed_text(state: &State, input: Option<Entity>) -> String {
+    input
+        .and_then(|input| state.entities.get::<TextInput>(input))
+        .map(|input| input.text.clone())
+        .unwrap_or_default()
+}
+
 // TODO: we might eventually want sprites to be more complex (animations, hiding, etc.), so we may
 // want to create a simple "UiImage" struct or something for these.
 fn init_sprites(state: &mut State) {
     let main_background = Sprite::new("Map001.nx/Back/login.img/back/11")
-        .with_transform(Transform::from_xyz(400.0, 300.0, 1.0));
+        .with_transform(Transform::from_xyz(400.0, 300.0, 1.0))
+        .with_layer(RenderLayer::Background);
 
     let side_trees = Sprite::new("Map001.nx/Back/login.img/back/35")
-        .with_transform(Transform::from_xyz(399.0, 260.0, 2.0));
+        .with_transform(Transform::from_xyz(399.0, 260.0, 2.0))
+        .with_layer(RenderLayer::Background);
 
+    // The logo is drawn with the backgrounds, but it isn't stretched like them, so it's kept sharp.
     let maplestory_logo = Sprite::new("MapPretty.nx/Back/login.img/ani/16/0")
-        .with_transform(Transform::from_xyz(394.0, 173.0, 2.0));
+        .with_transform(Transform::from_xyz(394.0, 173.0, 2.0))
+        .with_layer(RenderLayer::Background)
+        .with_sampler_policy(SamplerPolicy::Nearest);
 
     let signboard = Sprite::new("UI.nx/Login.img/Title/signboard")
-        .with_transform(Transform::from_xyz(391.0, 330.0, 10.0));
+        .with_transform(Transform::from_xyz(391.0, 330.0, 10.0))
+        .with_layer(RenderLayer::Ui);
 
     let border = Sprite::new("UI.nx/Login.img/Common/frame")
-        .with_transform(Transform::from_xyz(400.0, 300.0, 10.0));
-
-    state.sprites.push(main_background);
-    state.sprites.push(side_trees);
-    state.sprites.push(maplestory_logo);
-    state.sprites.push(signboard);
-    state.sprites.push(border);
+        .with_transform(Transform::from_xyz(400.0, 300.0, 10.0))
+        .with_layer(RenderLayer::Ui);
+
+    state.entities.spawn().with(main_background);
+    state.entities.spawn().with(side_trees);
+    state.entities.spawn().with(maplestory_logo);
+    state.entities.spawn().with(signboard);
+    state.entities.spawn().with(border);
 }
 
-fn init_buttons(state: &mut State) {
+fn init_buttons(state: &mut State) -> HashMap<Entity, LoginButton> {
     let login_button = Button::new("UI.nx/Login.img/Title/BtLogin")
-        .with_transform(Transform::from_xyz(454.0, 279.0, 11.0))
-        .with_on_click(|| log::info!("login"));
+        .with_transform(Transform::from_xyz(454.0, 279.0, 11.0));
 
     // TODO: is this supposed to be a checkbox?
     let save_login_id_button = Button::new("UI.nx/Login.img/Title/BtLoginIDSave")
-        .with_transform(Transform::from_xyz(303.0, 332.0, 11.0))
-        .with_on_click(|| log::info!("save_login_id"));
+        .with_transform(Transform::from_xyz(303.0, 332.0, 11.0));
 
     let find_login_id_button = Button::new("UI.nx/Login.img/Title/BtLoginIDLost")
-        .with_transform(Transform::from_xyz(375.0, 332.0, 11.0))
-        .with_on_click(|| log::info!("find_login_id"));
+        .with_transform(Transform::from_xyz(375.0, 332.0, 11.0));
 
     let find_password_button = Button::new("UI.nx/Login.img/Title/BtPasswdLost")
-        .with_transform(Transform::from_xyz(447.0, 332.0, 11.0))
-        .with_on_click(|| log::info!("find_password"));
+        .with_transform(Transform::from_xyz(447.0, 332.0, 11.0));
 
     let join_button = Button::new("UI.nx/Login.img/Title/BtNew")
-        .with_transform(Transform::from_xyz(291.0, 352.0, 11.0))
-        .with_on_click(|| log::info!("join"));
+        .with_transform(Transform::from_xyz(291.0, 352.0, 11.0));
 
     let website_button = Button::new("UI.nx/Login.img/Title/BtHomePage")
-        .with_transform(Transform::from_xyz(363.0, 352.0, 11.0))
-        .with_on_click(|| log::info!("website"));
+        .with_transform(Transform::from_xyz(363.0, 352.0, 11.0));
 
     let exit_button = Button::new("UI.nx/Login.img/Title/BtQuit")
-        .with_transform(Transform::from_xyz(435.0, 352.0, 11.0))
-        .with_on_click(|| std::process::exit(0));
-
-    state.buttons.push(login_button);
-    state.buttons.push(save_login_id_button);
-    state.buttons.push(find_login_id_button);
-    state.buttons.push(find_password_button);
-    state.buttons.push(join_button);
-    state.buttons.push(website_button);
-    state.buttons.push(exit_button);
+        .with_transform(Transform::from_xyz(435.0, 352.0, 11.0));
+
+    [
+        (
+            state.entities.spawn().with(login_button).id(),
+            LoginButton::Login,
+        ),
+        (
+            state.entities.spawn().with(save_login_id_button).id(),
+            LoginButton::SaveLoginId,
+        ),
+        (
+            state.entities.spawn().with(find_login_id_button).id(),
+            LoginButton::FindLoginId,
+        ),
+        (
+            state.entities.spawn().with(find_password_button).id(),
+            LoginButton::FindPassword,
+        ),
+        (
+            state.entities.spawn().with(join_button).id(),
+            LoginButton::Join,
+        ),
+        (
+            state.entities.spawn().with(website_button).id(),
+            LoginButton::Website,
+        ),
+        (
+            state.entities.spawn().with(exit_button).id(),
+            LoginButton::Exit,
+        ),
+    ]
+    .into_iter()
+    .collect()
 }
 
-fn init_text_inputs(state: &mut State) {
+fn init_text_inputs(state: &mut State) -> Entity {
     let username_input = TextInput::new(150, 24)
         .with_font(FontDescriptor::new("Arial", 13, Colour::white()))
         .with_transform(Transform::from_xyz(296.0, 279.0, 11.0));
 
-    state.text_inputs.push(username_input);
+    state.entities.spawn().with(username_input).id()
 }
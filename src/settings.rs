@@ -0,0 +1,71 @@
+use std::{collections::HashMap, fs, str::FromStr};
+
+/// The file settings are read from, relative to the working directory.
+const SETTINGS_PATH: &str = "settings.ini";
+
+/// Settings read from `settings.ini`, with `--key=value` command line arguments taking priority.
+///
+/// The file is made up of `key = value` lines, and lines starting with `#` are ignored.
+/// A command line argument without a value, ex. `--force_fallback_adapter`, is treated as `true`.
+pub struct Settings {
+    values: HashMap<String, String>,
+}
+
+impl Settings {
+    /// Loads the settings file (if it exists) and the command line arguments.
+    pub fn load() -> Self {
+        let mut settings = Self {
+            values: HashMap::new(),
+        };
+
+        match fs::read_to_string(SETTINGS_PATH) {
+            Ok(contents) => settings.parse_file(&contents),
+            Err(_) => log::info!("No {} found, using default settings", SETTINGS_PATH),
+        }
+
+        settings.parse_args(std::env::args().skip(1));
+        settings
+    }
+
+    fn parse_file(&mut self, contents: &str) {
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.split_once('=') {
+                Some((key, value)) => {
+                    self.values
+                        .insert(key.trim().to_string(), value.trim().to_string());
+                }
+                None => log::warn!("Ignoring invalid line in {}: {}", SETTINGS_PATH, line),
+            }
+        }
+    }
+
+    fn parse_args(&mut self, args: impl Iterator<Item = String>) {
+        for arg in args {
+            let Some(arg) = arg.strip_prefix("--") else {
+                continue;
+            };
+
+            let (key, value) = arg.split_once('=').unwrap_or((arg, "true"));
+            self.values.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    /// Gets a setting, or `default` if it isn't set or can't be parsed.
+    pub fn get_or<T: FromStr>(&self, key: &str, default: T) -> T {
+        let Some(value) = self.values.get(key) else {
+            return default;
+        };
+
+        match value.parse() {
+            Ok(value) => value,
+            Err(_) => {
+                log::warn!("Invalid value for {}: {}, using the default", key, value);
+                default
+            }
+        }
+    }
+}
@@ -8,33 +8,41 @@ use std::{
 use downcast_rs::{impl_downcast, Downcast};
 
 use crate::{
-    graphics::{
-        ui::{Button, Text, TextInput},
-        Sprite,
-    },
-    resource::{Cursor, WindowProxy},
+    entity::Entities,
+    event::Events,
+    resource::{Cursor, ScreenEffects, WindowProxy},
+    scene::Scene,
 };
 
-// TODO: maybe we can have a "UI" field that contains buttons, images, text fields, etc.
 pub struct State {
     resources: HashMap<ResourceTypeId, RefCell<Box<dyn Resource>>>,
-    pub sprites: Vec<Sprite>,
-    pub buttons: Vec<Button>,
-    pub text_inputs: Vec<TextInput>,
-    pub text: Vec<Text>,
+
+    /// Every game object, ex. sprites, buttons and text.
+    pub entities: Entities,
+
+    /// The scene to switch to at the end of the current update, see `State::change_scene`.
+    pub next_scene: Option<Box<dyn Scene>>,
+
+    /// Swaps the buffers of each registered event type, see `State::add_event`.
+    event_updaters: Vec<fn(&State)>,
 }
 
 impl State {
     pub fn new() -> Self {
         Self {
             resources: HashMap::new(),
-            sprites: Vec::new(),
-            buttons: Vec::new(),
-            text_inputs: Vec::new(),
-            text: Vec::new(),
+            entities: Entities::new(),
+            next_scene: None,
+            event_updaters: Vec::new(),
         }
     }
 
+    /// Fades out and switches to `scene` once the current update finishes, despawning everything
+    /// the current scene spawned.
+    pub fn change_scene(&mut self, scene: impl Scene + 'static) {
+        self.next_scene = Some(Box::new(scene));
+    }
+
     pub fn insert_resource<T: Resource>(&mut self, resource: T) -> &mut Self {
         self.resources
             .insert(ResourceTypeId::of::<T>(), RefCell::new(Box::new(resource)));
@@ -56,6 +64,33 @@ impl State {
             .map(|x| RefMut::map(x.borrow_mut(), |inner| inner.downcast_mut::<T>().unwrap()))
     }
 
+    /// Registers an event type, so it can be sent and read by systems.
+    pub fn add_event<T: Resource>(&mut self) -> &mut Self {
+        self.event_updaters.push(update_events::<T>);
+        self.insert_resource(Events::<T>::new())
+    }
+
+    pub fn send_event<T: Resource>(&self, event: T) {
+        self.events_mut::<T>().send(event);
+    }
+
+    pub fn events<T: Resource>(&self) -> Ref<'_, Events<T>> {
+        self.get_resource::<Events<T>>()
+            .expect("event type should be registered with add_event")
+    }
+
+    fn events_mut<T: Resource>(&self) -> RefMut<'_, Events<T>> {
+        self.get_resource_mut::<Events<T>>()
+            .expect("event type should be registered with add_event")
+    }
+
+    /// Drops the events sent two updates ago, this should be called at the start of every update.
+    pub fn update_events(&self) {
+        for update in self.event_updaters.iter() {
+            (update)(self);
+        }
+    }
+
     pub fn cursor(&self) -> RefMut<Cursor> {
         self.get_resource_mut::<Cursor>()
             .expect("Cursor should exist")
@@ -65,6 +100,15 @@ impl State {
         self.get_resource_mut::<WindowProxy>()
             .expect("WindowProxy should exist")
     }
+
+    pub fn screen_effects(&self) -> RefMut<'_, ScreenEffects> {
+        self.get_resource_mut::<ScreenEffects>()
+            .expect("ScreenEffects should exist")
+    }
+}
+
+fn update_events<T: Resource>(state: &State) {
+    state.events_mut::<T>().update();
 }
 
 pub trait Resource: 'static + Downcast {}
@@ -1,10 +1,15 @@
-use winit::event::MouseButton;
+use winit::{
+    event::MouseButton,
+    keyboard::{Key, NamedKey},
+};
 
 use crate::{
     component::Transform,
+    entity::{Children, Entity},
+    event::{ButtonClicked, EventReader, KeyPressed, TextSubmitted},
     graphics::{
-        ui::{ButtonState, Text},
-        RenderableV2,
+        ui::{Button, ButtonState, Text, TextInput},
+        RenderLayer, RenderableV2, Shape,
     },
     resource::AssetManager,
     state::State,
@@ -15,7 +20,10 @@ pub fn button_system(state: &mut State) {
     let (mouse_x, mouse_y) = state.cursor().position();
     let is_clicking = state.cursor().is_button_pressed(MouseButton::Left);
 
-    for button in state.buttons.iter_mut() {
+    // Events can't be sent while the buttons are borrowed, so they're sent afterwards.
+    let mut clicked = Vec::new();
+
+    for (entity, button) in state.entities.query_mut::<Button>() {
         if button.state == ButtonState::Disabled {
             continue;
         }
@@ -30,26 +38,80 @@ pub fn button_system(state: &mut State) {
         {
             if is_clicking {
                 button.state = ButtonState::Pressed;
-
-                // FIXME: this clicks multiple times.
-                if button.on_click.is_some() {
-                    (button.on_click.unwrap())();
-                }
             } else {
+                // The button is clicked once the mouse is released over it, not while it's held.
+                if button.state == ButtonState::Pressed {
+                    clicked.push(entity);
+                }
+
                 button.state = ButtonState::Hovered;
             }
         } else {
             button.state = ButtonState::Default;
         }
     }
+
+    for entity in clicked {
+        state.send_event(ButtonClicked(entity));
+    }
+}
+
+/// The keys pressed since the text system last ran.
+#[derive(Default)]
+struct TextSystemState {
+    keys: EventReader<KeyPressed>,
 }
 
 /// System for handling text inputs.
 pub fn text_system(state: &mut State) {
-    for input in state.text_inputs.iter_mut() {
+    if state.get_resource::<TextSystemState>().is_none() {
+        state.insert_resource(TextSystemState::default());
+    }
+
+    let keys: Vec<Key> = state
+        .get_resource_mut::<TextSystemState>()
+        .unwrap()
+        .keys
+        .read(&state.events::<KeyPressed>())
+        .map(|KeyPressed(event)| event.logical_key.clone())
+        .collect();
+
+    // Text can't be spawned or despawned while the inputs are borrowed, so it's done afterwards.
+    // The same goes for sending events.
+    let mut redrawn = Vec::new();
+    let mut submitted = Vec::new();
+
+    for (input_entity, input) in state.entities.query_mut::<TextInput>() {
         // TODO: this should be the font/font size/colour of the input
         let font = AssetManager::get_font(&input.font_descriptor).unwrap();
 
+        // TODO: only the focused input should be typed in, once inputs can be focused.
+        for key in keys.iter() {
+            match key {
+                Key::Named(NamedKey::Enter) => submitted.push(TextSubmitted {
+                    input: input_entity,
+                    text: input.text.clone(),
+                }),
+                Key::Named(NamedKey::Backspace) => {
+                    input.changed |= input.text.pop().is_some();
+                }
+                Key::Named(NamedKey::Space) => {
+                    input.text.push(' ');
+                    input.changed = true;
+                }
+                Key::Character(characters) => {
+                    // Characters the font can't draw are ignored.
+                    for character in characters.chars() {
+                        if font.characters.contains_key(&character) {
+                            input.text.push(character);
+                            input.changed = true;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
         // The input hasn't changed, ex. nothing was typed while focused.
         if !input.changed {
             continue;
@@ -57,6 +119,8 @@ pub fn text_system(state: &mut State) {
 
         input.changed = false;
 
+        let mut text = Vec::new();
+
         // TODO: we should move all of this logic to the renderer manager.
         // this system should really only handle updating the input's text, focus, etc.
         let mut current_pos = 0.0;
@@ -78,11 +142,45 @@ pub fn text_system(state: &mut State) {
 
             current_pos = current_pos + character.width + 2.0;
 
-            let ui_text = Text::new(character, font).with_transform(transform);
+            // Clip the text to the input's box, so it doesn't draw outside of it when overflowing.
+            let ui_text = Text::new(character, font)
+                .with_transform(transform)
+                .with_clip(input.clip_rect());
 
             // I'm thinking there should be some shared "text" struct/component that is rendered.
             // the text component should be able to be rendered by text inputs, and static text (player names, etc.)
-            state.text.push(ui_text);
+            text.push(ui_text);
         }
+
+        // Draw the caret after the last character.
+        let caret = Shape::rect(
+            input.transform.x + current_pos,
+            input.transform.y,
+            1.0,
+            input.clip_rect().height,
+            input.font_descriptor.colour(),
+        )
+        .with_layer(RenderLayer::Ui)
+        .with_clip(input.clip_rect());
+
+        redrawn.push((input_entity, text, caret));
+    }
+
+    for event in submitted {
+        state.send_event(event);
+    }
+
+    // The input's text and caret are its children, so they're replaced when it's redrawn and
+    // despawned along with it.
+    for (input_entity, text, caret) in redrawn {
+        state.entities.despawn_children(input_entity);
+
+        let mut children: Vec<Entity> = text
+            .into_iter()
+            .map(|ui_text| state.entities.spawn().with(ui_text).id())
+            .collect();
+        children.push(state.entities.spawn().with(caret).id());
+
+        state.entities.insert(input_entity, Children(children));
     }
 }